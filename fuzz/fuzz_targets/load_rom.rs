@@ -0,0 +1,12 @@
+#![no_main]
+
+// cargo +nightly fuzz run load_rom
+use libfuzzer_sys::fuzz_target;
+use nass::Emulator;
+
+fuzz_target!(|data: &[u8]| {
+    let mut emulator = Emulator::new();
+    // A blank BIOS, so disk images get past the BIOS check.
+    emulator.set_bios_len(0x2000);
+    let _ = emulator.load_rom(data);
+});
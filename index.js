@@ -10,9 +10,14 @@ const imports = {
 
 let buffer = new Uint8Array();
 let wasm = {};
+let emulator = null;
 let running = false;
+let halted = false;
 
-WebAssembly.instantiateStreaming(fetch('target/wasm32-unknown-unknown/release/nass.wasm'), imports).then(obj => { wasm = obj.instance.exports; });
+WebAssembly.instantiateStreaming(fetch('target/wasm32-unknown-unknown/release/nass.wasm'), imports).then(obj => {
+  wasm = obj.instance.exports;
+  emulator = wasm.create_emulator();
+});
 
 const canvas = document.getElementById("nass-canvas");
 canvas.width = WIDTH*2;
@@ -27,6 +32,16 @@ const pctx = palette_canvas.getContext('2d');
 palette_canvas.width = 16*PALETTE_SIZE;
 
 document.getElementById("rom-input").onchange = getFile;
+document.getElementById("bios-input").onchange = getBios;
+document.getElementById("database-input").onchange = getDatabase;
+document.getElementById("reset-button").onclick = () => {
+  if (!running) return;
+  wasm.reset(emulator);
+  halted = false;
+};
+document.getElementById("disk-button").onclick = switchDiskSide;
+document.getElementById("save-disk-button").onclick = saveDisk;
+document.getElementById("dip-input").onchange = setDipSwitches;
 
 const getRgba = (r, g, b, a) => `rgba(${r}, ${g}, ${b}, ${a})`;
 
@@ -60,29 +75,149 @@ const drawPalettes = (getColor) => {
   }
 }
 
+const loadFile = (file) => {
+  return new Promise((resolve, reject) => {
+    const reader = new FileReader();
+    reader.onload = (e) => resolve(e.target.result);
+    reader.onerror = (_) => reject(reader.error);
+    reader.readAsArrayBuffer(file);
+  })
+}
+
+function getBios() {
+  const file = document.getElementById("bios-input").files[0];
+  loadFile(file).then(bios_buffer => {
+    const bios = new Uint8Array(bios_buffer);
+    wasm.set_bios_length(emulator, bios.length);
+    buffer = new Uint8Array(wasm.memory.buffer);
+    buffer.set(bios, wasm.get_bios_pointer(emulator));
+  })
+}
+
+// Extra database.txt entries, used from the next ROM loaded.
+function getDatabase() {
+  const file = document.getElementById("database-input").files[0];
+  loadFile(file).then(database_buffer => {
+    const database = new Uint8Array(database_buffer);
+    wasm.set_database_length(emulator, database.length);
+    buffer = new Uint8Array(wasm.memory.buffer);
+    buffer.set(database, wasm.get_database_pointer(emulator));
+  })
+}
+
+function switchDiskSide() {
+  const sides = running ? wasm.get_disk_sides(emulator) : 0;
+  if (sides == 0) return;
+  const side = (wasm.get_disk_side(emulator) + 1) % sides;
+  // The BIOS only notices a new disk after it has seen the drive empty.
+  wasm.eject_disk(emulator);
+  setTimeout(() => wasm.insert_disk(emulator, side), 1000);
+}
+
+function setDipSwitches() {
+  if (!running || !wasm.is_vs_system(emulator)) return;
+  wasm.set_dip_switches(emulator, document.getElementById("dip-input").value & 0xFF);
+}
+
+function saveDisk() {
+  const length = running ? wasm.save_disk(emulator) : 0;
+  if (length == 0) return;
+  buffer = new Uint8Array(wasm.memory.buffer);
+  const pointer = wasm.get_disk_image_pointer(emulator);
+  const blob = new Blob([buffer.slice(pointer, pointer + length)]);
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(blob);
+  link.download = document.getElementById("rom-input").files[0].name;
+  link.click();
+}
+
+// Bank windows and registers of the mapper, for a memory map viewer.
+function getDebugState() {
+  if (!running) return null;
+  const length = wasm.get_debug_state(emulator);
+  buffer = new Uint8Array(wasm.memory.buffer);
+  const pointer = wasm.get_debug_state_pointer(emulator);
+  return JSON.parse(new TextDecoder().decode(buffer.subarray(pointer, pointer + length)));
+}
+
+// A JAM opcode stopped the CPU, only reset gets it going again.
+const reportHalt = () => {
+  const halt = wasm.get_halt(emulator);
+  if (halted || halt < 0) return;
+  halted = true;
+  const hex = (value, digits) => value.toString(16).toUpperCase().padStart(digits, "0");
+  alert(`CPU halted at $${hex(halt >> 8, 4)} by opcode $${hex(halt & 0xFF, 2)}`);
+}
+
+let saveKey = null;
+
+const getSaveRam = () => {
+  buffer = new Uint8Array(wasm.memory.buffer);
+  const pointer = wasm.get_save_ram_pointer(emulator);
+  return buffer.subarray(pointer, pointer + wasm.get_save_ram_length(emulator));
+}
+
+const loadSaveRam = () => {
+  const save = localStorage.getItem(saveKey);
+  if (!wasm.has_battery(emulator) || save == null) return;
+  const bytes = Uint8Array.from(atob(save), (c) => c.charCodeAt(0));
+  getSaveRam().set(bytes.subarray(0, wasm.get_save_ram_length(emulator)));
+}
+
+const flushSaveRam = () => {
+  if (!running || !wasm.has_battery(emulator)) return;
+  localStorage.setItem(saveKey, btoa(String.fromCharCode(...getSaveRam())));
+}
+
+setInterval(flushSaveRam, 5000);
+window.addEventListener('beforeunload', flushSaveRam);
+
+const CORRECTIONS = ["mapper", "submapper", "mirroring", "PRG RAM", "CHR RAM", "battery", "timing", "input device", "console"];
+
+const logCorrections = (corrections) => {
+  const applied = CORRECTIONS.filter((_, bit) => corrections & (1 << bit));
+  if (applied.length > 0) console.log(`Header corrected by game database: ${applied.join(", ")}`);
+}
+
+// Indexed by LoadError::code.
+const LOAD_ERRORS = [
+  null,
+  "Only NES, UNIF and FDS files supported.",
+  "ROM header is truncated.",
+  "ROM file is shorter than its header says.",
+  "ROM trainer is truncated.",
+  "Mapper not implemented.",
+  "UNIF board not implemented.",
+  "FDS BIOS (8KB) required.",
+  "FDS image has no disk sides.",
+  "FDS disk side is too full to save in its image format.",
+];
+
 function getFile() {
   const file = document.getElementById("rom-input").files[0];
-  const loadFile = (file) => {
-    return new Promise((resolve, reject) => {
-      const reader = new FileReader();
-      reader.onload = (e) => resolve(e.target.result);
-      reader.onerror = (_) => reject(reader.error);
-      reader.readAsArrayBuffer(file);
-    })
-  }
   loadFile(file).then(rom_buffer => { 
     const rom = new Uint8Array(rom_buffer);
-    wasm.set_rom_length(rom.length);
+    flushSaveRam();
+    wasm.set_rom_length(emulator, rom.length);
     buffer = new Uint8Array(wasm.memory.buffer);
-    buffer.set(rom, wasm.get_rom_pointer())
-    wasm.disassemble();
-    wasm.reset();
+    buffer.set(rom, wasm.get_rom_pointer(emulator))
+    const error = wasm.disassemble(emulator);
+    if (error != 0) {
+      alert(LOAD_ERRORS[error] ?? "Unknown error.");
+      return;
+    }
+    logCorrections(wasm.get_corrections(emulator));
+    saveKey = `nass-save-${file.name}`;
+    loadSaveRam();
     buffer = new Uint8Array(wasm.memory.buffer);
     running = true;
+    halted = false;
+    setDipSwitches();
     const fn = () => {
-      drawCells(wasm.get_frame_pointer());
-      drawPalettes(wasm.get_color);
-      wasm.step();
+      drawCells(wasm.get_frame_pointer(emulator));
+      drawPalettes((index) => wasm.get_color(emulator, index));
+      wasm.run_frame(emulator);
+      reportHalt();
       requestAnimationFrame(fn); 
     }
     requestAnimationFrame(fn);
@@ -112,10 +247,26 @@ const getButton = (key) => {
     }
 }
 
+const getCoin = (key) => {
+    switch (key) {
+      case "5":
+        return 0b001;
+      case "6":
+        return 0b010;
+      case "9":
+        return 0b100;
+      default:
+        return 0;
+    }
+}
+
 const toggleButton = (event) => {
   const button = getButton(event.key);
   if (button != 0 && running) 
-    wasm.toggle_button(button);
+    wasm.toggle_button(emulator, button);
+  const coin = getCoin(event.key);
+  if (coin != 0 && running && wasm.is_vs_system(emulator))
+    wasm.toggle_coin(emulator, coin);
 }
 
 document.addEventListener('keyup', toggleButton);
@@ -1,87 +1,390 @@
-use crate::ppu::PPU;
-pub use crate::cpu::joypad::*;
+use crate::ppu::{ PPU, Model };
+pub use crate::cpu::{ joypad::*, vs_system::*, dmc::* };
 use crate::mapper::*;
-use Interrupt::*;
+use crate::cpu::Bus;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-pub enum Interrupt {
-    Nmi,
-    Irq,
+const RAM_SIZE: usize = 0x800;
+const CPU_FREQUENCY: usize = 1_789_773;
+const SAMPLE_RATE: usize = 44_100;
+
+// What the 2KB of RAM holds at power on. It is undefined on hardware, games that
+// expect it cleared break on some consoles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RamInit {
+    Zeros,
+    Ones, // All $FF
+    Pattern, // Four $00 then four $FF, as on many consoles
+    Random(u64), // Seed
 }
 
-const RAM_SIZE: usize = 0x800;
+impl RamInit {
+    fn fill(&self, ram: &mut [u8]) {
+        match *self {
+            RamInit::Zeros => ram.fill(0),
+            RamInit::Ones => ram.fill(0xFF),
+            RamInit::Pattern => ram.iter_mut().enumerate().for_each(|(i, byte)| *byte = if i & 0x04 == 0 { 0x00 } else { 0xFF }),
+            RamInit::Random(seed) => {
+                // SplitMix64, the same seed always gives the same RAM.
+                let mut state = seed;
+                for byte in ram.iter_mut() {
+                    state = state.wrapping_add(0x9E3779B97F4A7C15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+                    *byte = (z ^ (z >> 31)) as u8;
+                }
+            }
+        }
+    }
+}
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct BUS {
     ram: [u8; RAM_SIZE],
     pub mapper: Mapper_,
     pub ppu: PPU,
-    pub interrupt: Option<Interrupt>,
-    pub suspend: bool,
-    pub joypad: Joypad,
-    pub rom: *const u8
+    nmi: bool, // NMI edge, latched by the CPU
+    open_bus: u8, // Last value on the data bus, read back from anything that doesn't drive it
+    oam_dma: Option<u8>, // Page written to $4014, copied once the CPU is halted
+    oam_copy: Option<(u16, usize)>, // Next address to copy and bytes left
+    oam_value: Option<u8>, // Byte read, waiting for its put cycle
+    dma_halted: bool, // The CPU has been halted by a DMA
+    dmc_dma: Option<u16>, // Sample address the DMC is waiting on
+    dmc_delay: u8, // Halt and dummy cycles the DMC DMA still has to wait
+    pub dmc: DMC,
+    pub joypads: [Joypad; 2],
+    pub vs_system: Option<VsSystem>,
+    pub audio: Vec<f32>,
+    sample_timer: usize,
 }
 
 impl BUS {
-    pub fn new(rom: *const u8, mapper: Mapper_, ppu: PPU) -> Self {
-        let bus = BUS {
+    pub fn new(mapper: Mapper_, ppu: PPU) -> Self {
+        BUS {
             ram: [0; RAM_SIZE],
             mapper,
             ppu,
-            suspend: false,
-            interrupt: None,
-            rom,
-            joypad: Joypad::new()
-        };
-        bus
+            oam_dma: None,
+            oam_copy: None,
+            oam_value: None,
+            dma_halted: false,
+            dmc_dma: None,
+            dmc_delay: 0,
+            dmc: DMC::new(),
+            nmi: false,
+            open_bus: 0,
+            joypads: [Joypad::new(), Joypad::new()],
+            vs_system: None,
+            audio: Vec::new(),
+            sample_timer: 0,
+        }
+    }
+
+    pub fn power_on(&mut self, ram_init: RamInit) {
+        ram_init.fill(&mut self.ram);
+        self.ppu.power_on();
+        self.nmi = false;
+        self.open_bus = 0;
+        self.oam_dma = None;
+        self.oam_copy = None;
+        self.oam_value = None;
+        self.dma_halted = false;
+        self.dmc_dma = None;
+        self.dmc = DMC::new();
+        self.joypads.iter_mut().for_each(Joypad::power_on);
+        if let Some(vs_system) = self.vs_system.as_mut() { vs_system.coin_counter = false; }
+    }
+
+    // Reset button: the mapper and the PPU registers, RAM is kept.
+    pub fn reset(&mut self) {
+        self.dmc.write_4015(0x00);
+        self.mapper.reset();
+        self.ppu.reset();
+    }
+
+    // Arcade hardware the cartridge was made for.
+    pub fn set_console(&mut self, console: Console) {
+        if let Console::VsSystem { ppu, .. } = console {
+            self.ppu.model = Model::from_vs_ppu(ppu);
+            self.vs_system = Some(VsSystem::new());
+        }
     }
 
-    pub fn write(&mut self, addr: u16, value: u8) {
+    // The byte at `addr` goes to the DMC after the CPU is halted, the dummy cycle and a get cycle.
+    fn request_dmc_dma(&mut self, addr: u16) {
+        self.dmc_dma = Some(addr);
+        self.dmc_delay = 2;
+    }
+}
+
+impl Bus for BUS {
+    fn write(&mut self, addr: u16, value: u8) {
+        self.open_bus = value;
+        if let 0x2000..=0x2007 = addr { self.ppu.refresh_io_latch(value, 0xFF); }
+        if let 0x4000..=0x401F = addr { self.mapper.write_register(addr, value); }
         match addr {
             0x0000..=0x1FFF => self.ram[(addr as usize) & 0x07FF] = value,
-            0x2000 => if self.ppu.write_to_ctrl(value) { self.interrupt = Some(Nmi) },
-            0x2001 => self.ppu.mask.update(value),
+            0x2000..=0x2001 => {
+                let swapped = matches!(self.ppu.model, Model::Rc2C05(_));
+                if (addr == 0x2000) != swapped {
+                    if self.ppu.write_to_ctrl(value) { self.nmi = true }
+                } else {
+                    self.ppu.mask.update(value);
+                }
+            },
             0x2003 => self.ppu.oam_addr = value,
             0x2004 => self.ppu.write_to_oam(value),
             0x2005 => self.ppu.write_to_scroll(value),
             0x2006 => self.ppu.write_to_ppu_addr(value),
             0x2007 => self.ppu.write_data(value, &mut self.mapper),
             0x2008..=0x3FFF => self.write(addr & 0x2007, value),
-            0x4016 => self.joypad.write(value),
-            0x4014 => {
-                self.suspend = true;
-                let addr = ((value as u16) & 0xFF) << 8;
-                for i in 0..=0xFF {
-                    let value = self.read(addr+i);
-                    self.write(0x2004, value);
-                }
+            0x4016 => self.joypads.iter_mut().for_each(|joypad| joypad.write(value)),
+            0x4010..=0x4013 => self.dmc.write(addr, value),
+            0x4014 => self.oam_dma = Some(value),
+            0x4015 => self.dmc.write_4015(value),
+            0x4020..=0xFFFF => {
+                if let (0x4020, Some(vs_system)) = (addr, self.vs_system.as_mut()) { vs_system.write_4020(value); }
+                self.mapper.write_prg(addr, value);
             },
-            0x4020..=0xFFFF => self.mapper.write_prg(addr, value),
             _ => ()
         }
     }
 
-    pub fn read(&mut self, addr: u16) -> u8 { 
-        match addr {
+    fn read(&mut self, addr: u16) -> u8 { 
+        let value = match addr {
             0x0000..=0x1FFF => self.ram[addr as usize & 0x07FF],
-            0x2000 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => 0,
+            0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 => self.ppu.io_latch(),
             0x2002 => self.ppu.read_status(),
             0x2004 => self.ppu.read_oam(),
-            0x2007 => self.ppu.read_data(self.rom, &self.mapper),
-            0x4016 => self.joypad.read(),
+            0x2007 => self.ppu.read_data(&self.mapper),
+            0x4015 => (self.open_bus & 0x20) | self.dmc.read_4015(),
+            // Controllers only drive the low bits (VS. System: all but bit 7 on $4016, all on $4017).
+            0x4016 => match self.vs_system.as_ref() {
+                Some(vs_system) => (self.open_bus & 0x80) | self.joypads[0].read() | vs_system.read_4016(),
+                None => (self.open_bus & 0xE0) | self.joypads[0].read(),
+            },
+            0x4017 => match self.vs_system.as_ref() {
+                Some(vs_system) => self.joypads[1].read() | vs_system.read_4017(),
+                None => (self.open_bus & 0xE0) | self.joypads[1].read(),
+            },
             0x2008..=0x3FFF => self.read(addr & 0x2007),
-            0x4020..=0xFFFF => self.mapper.read_prg(self.rom, addr),
-            _ => 0
-        }
+            0x4020..=0xFFFF => {
+                self.mapper.get_cartridge_mut().open_bus = self.open_bus;
+                self.mapper.read_prg(addr)
+            },
+            _ => self.open_bus
+        };
+        self.open_bus = value;
+        value
     }
 
-    pub fn tick(&mut self, cycles: usize) {
-        for _ in 0..(3*cycles) {
-            self.ppu.tick(self.rom, &mut self.mapper);
+    // One CPU cycle: the mapper, the DMC and audio once, the PPU three dots.
+    fn tick(&mut self) {
+        self.mapper.tick();
+        if let Some(addr) = self.dmc.tick() { self.request_dmc_dma(addr); }
+        self.sample_timer += SAMPLE_RATE;
+        if self.sample_timer >= CPU_FREQUENCY {
+            self.sample_timer -= CPU_FREQUENCY;
+            self.audio.push((self.mapper.get_audio_output() + self.dmc.output()).min(1.0));
+        }
+        for _ in 0..3 {
+            self.ppu.tick(&mut self.mapper);
             if self.ppu.nmi_occured {
-                self.interrupt = Some(Nmi); 
+                self.nmi = true; 
                 self.ppu.nmi_occured = false;
-                // break;
             }
         }
     }
+
+    fn nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi)
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.irq() || self.dmc.irq
+    }
+
+    // OAM and DMC DMA halt the CPU on its next read and take over the bus. Gets are on
+    // even cycles and puts on odd ones, the DMC wins a get cycle it is ready for.
+    // While it waits the CPU repeats its read, except on the controller ports where
+    // that would clock the shift registers.
+    // https://www.nesdev.org/wiki/DMA
+    fn halt(&mut self, addr: u16, cycle: usize) -> bool {
+        if self.oam_dma.is_none() && self.oam_copy.is_none() && self.dmc_dma.is_none() {
+            self.dma_halted = false;
+            return false
+        }
+        let get = cycle & 1 == 0;
+        let repeat = !matches!(addr, 0x4016 | 0x4017);
+        if !self.dma_halted {
+            self.dma_halted = true;
+            self.oam_copy = self.oam_dma.take().map(|page| ((page as u16) << 8, 256));
+            if repeat { self.read(addr); }
+        } else {
+            match (self.dmc_dma, self.oam_copy, self.oam_value) {
+                (Some(dmc_addr), _, _) if get && self.dmc_delay == 0 => {
+                    let value = self.read(dmc_addr);
+                    self.dmc.fill(value);
+                    self.dmc_dma = None;
+                },
+                (_, Some((src, _)), None) if get => self.oam_value = Some(self.read(src)),
+                (_, Some((src, left)), Some(value)) if !get => {
+                    self.write(0x2004, value);
+                    self.oam_value = None;
+                    self.oam_copy = if left > 1 { Some((src.wrapping_add(1), left - 1)) } else { None };
+                },
+                // Dummy and alignment cycles.
+                _ => if repeat { self.read(addr); }
+            }
+        }
+        self.dmc_delay = self.dmc_delay.saturating_sub(1);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // iNES 1.0 image, 32KB PRG and 16KB CHR. Each PRG byte holds the high byte of its
+    // offset, each CHR byte its 8KB bank number.
+    fn bus(flags_6: u8, flags_7: u8) -> BUS {
+        let mut bytes = vec![b'N', b'E', b'S', 0x1A, 2, 2, flags_6, flags_7, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend((0..0x8000).map(|i| (i >> 8) as u8));
+        bytes.extend((0..0x4000).map(|i| (i / 0x2000) as u8));
+        let (mapper, console, _) = crate::mapper::new(&bytes, &[], "").unwrap();
+        let mut bus = BUS::new(mapper, PPU::new());
+        bus.set_console(console);
+        bus
+    }
+
+    #[test]
+    fn mapper_99_without_vs_header() {
+        let mut bus = bus(0x30, 0x60);
+        assert!(bus.vs_system.is_none());
+        bus.write(0x4016, 0x04);
+        assert_eq!(bus.mapper.read_chr(0x0000), 1);
+        bus.write(0x4016, 0x00);
+        assert_eq!(bus.mapper.read_chr(0x0000), 0);
+    }
+
+    #[test]
+    fn vs_system_without_database_entry() {
+        // iNES 1.0 VS. flag, mapper 99, no database entry: default PPU but the cabinet inputs work.
+        let mut bus = bus(0x30, 0x61);
+        assert!(matches!(bus.ppu.model, Model::Rp2C03));
+        let vs_system = bus.vs_system.as_mut().unwrap();
+        vs_system.coins = COIN_1 | SERVICE;
+        vs_system.dip_switches = 0b1010_0110;
+        assert_eq!(bus.read(0x4016) & 0x7C, 0x24 | 0b10 << 3);
+        assert_eq!(bus.read(0x4017) & 0xFC, 0b1010_0100);
+        bus.vs_system.as_mut().unwrap().coins = COIN_2;
+        assert_eq!(bus.read(0x4016) & 0x64, 0x40);
+        bus.write(0x4020, 0x01);
+        assert!(bus.vs_system.as_ref().unwrap().coin_counter);
+        // The board still banks CHR on $4016.
+        bus.write(0x4016, 0x04);
+        assert_eq!(bus.mapper.read_chr(0x0000), 1);
+    }
+
+    // Runs the CPU's side of a DMA: halted on a read of $8000 at `cycle` (even cycles are gets), calls
+    // `during` on every stalled cycle. Returns the stall length.
+    fn stall(bus: &mut BUS, mut cycle: usize, mut during: impl FnMut(&mut BUS, usize)) -> usize {
+        let start = cycle;
+        while bus.halt(0x8000, cycle) {
+            bus.tick();
+            cycle += 1;
+            during(bus, cycle - start);
+        }
+        cycle - start
+    }
+
+    fn oam(bus: &mut BUS) -> Vec<u8> {
+        (0..=0xFF).map(|addr| {
+            bus.write(0x2003, addr);
+            bus.read(0x2004)
+        }).collect()
+    }
+
+    #[test]
+    fn oam_dma() {
+        for (cycle, stalled) in [(0, 514), (1, 513)] {
+            let mut bus = bus(0, 0);
+            for n in 0..0x100 { bus.write(0x0200 + n, n as u8); }
+            bus.write(0x4014, 0x02);
+            assert_eq!(stall(&mut bus, cycle, |_, _| ()), stalled);
+            let expected: Vec<u8> = (0..=0xFF).map(|n: u8| if n & 0x03 == 2 { n & 0xE3 } else { n }).collect();
+            assert_eq!(oam(&mut bus), expected);
+        }
+    }
+
+    #[test]
+    fn dmc_dma_during_oam_dma() {
+        let mut bus = bus(0, 0);
+        for n in 0..0x100 { bus.write(0x0200 + n, n as u8); }
+        bus.write(0x4014, 0x02);
+        // The DMC steals a get cycle and the OAM DMA realigns: 2 more cycles.
+        bus.write(0x4012, 0x04);
+        let stalled = stall(&mut bus, 0, |bus, cycles| if cycles == 99 { bus.write(0x4015, 0x10) });
+        assert_eq!(stalled, 516);
+        assert_eq!(bus.dmc.buffer, Some(0x41));
+        let expected: Vec<u8> = (0..=0xFF).map(|n: u8| if n & 0x03 == 2 { n & 0xE3 } else { n }).collect();
+        assert_eq!(oam(&mut bus), expected);
+    }
+
+    #[test]
+    fn dmc_dma() {
+        // Halt, dummy, (alignment,) get.
+        for (cycle, stalled) in [(0, 3), (1, 4)] {
+            let mut bus = bus(0, 0);
+            bus.write(0x4012, 0xFF);
+            bus.write(0x4015, 0x10);
+            bus.tick();
+            assert_eq!(stall(&mut bus, cycle, |_, _| ()), stalled);
+            assert_eq!(bus.dmc.buffer, Some(0x7F));
+        }
+    }
+
+    #[test]
+    fn dmc_sample() {
+        // A CPU reading $8000 every cycle it isn't halted.
+        fn run(bus: &mut BUS, cycles: usize) -> usize {
+            (0..cycles).filter(|&cycle| {
+                let halted = bus.halt(0x8000, cycle);
+                if !halted { bus.read(0x8000); }
+                bus.tick();
+                halted
+            }).count()
+        }
+        let mut bus = bus(0, 0);
+        // IRQ at the end, fastest rate (54 cycles a bit), 17 bytes from $C000.
+        bus.write(0x4010, 0x8F);
+        bus.write(0x4012, 0x00);
+        bus.write(0x4013, 0x01);
+        bus.write(0x4015, 0x10);
+        assert_eq!(bus.read(0x4015) & 0x90, 0x10);
+        // A byte is fetched whenever the buffer empties, one every 8 bits after the first.
+        let stalled = run(&mut bus, 16 * 8 * 54);
+        assert!((3 * 16..=4 * 16).contains(&stalled));
+        assert!(!bus.irq());
+        run(&mut bus, 8 * 54);
+        assert!(bus.irq());
+        assert_eq!(bus.read(0x4015) & 0x90, 0x80);
+        assert_eq!(run(&mut bus, 16 * 8 * 54), 0);
+        bus.write(0x4015, 0x00);
+        assert!(!bus.irq());
+
+        // Looping samples never end.
+        bus.write(0x4010, 0x4F);
+        bus.write(0x4015, 0x10);
+        run(&mut bus, 40 * 8 * 54);
+        assert!(!bus.irq());
+        assert_eq!(bus.read(0x4015) & 0x10, 0x10);
+        // Reset stops it.
+        bus.reset();
+        assert_eq!(bus.read(0x4015) & 0x10, 0);
+
+        bus.write(0x4011, 0x40);
+        assert!(bus.dmc.output() > 0.0);
+    }
 }
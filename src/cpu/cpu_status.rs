@@ -52,10 +52,6 @@ impl CPUStatus {
         self.set(CPUStatus::NEGATIVE, condition);
     }
 
-    pub fn set_break(&mut self, condition: bool) {
-        self.set(CPUStatus::BREAK, condition);
-    }
-
     pub fn interrupt(&self) -> bool {
         self.intersects(CPUStatus::INTERRUPT_DISABLE)
     }
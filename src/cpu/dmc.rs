@@ -0,0 +1,149 @@
+// APU delta modulation channel, the one APU unit that reads memory. Samples are fetched
+// by DMC DMA (see BUS::halt), the other APU channels aren't emulated.
+// https://www.nesdev.org/wiki/APU_DMC
+
+// CPU cycles per output bit (NTSC).
+const RATES: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct DMC {
+    irq_enabled: bool,
+    looping: bool,
+    rate: u16,
+    timer: u16,
+    output: u8, // 7-bit level
+    sample_address: u16,
+    sample_length: u16,
+    address: u16, // Next byte to fetch
+    bytes_left: u16,
+    pub buffer: Option<u8>,
+    fetching: bool, // A DMA is on its way to fill the buffer
+    shift: u8,
+    bits_left: u8,
+    silence: bool,
+    pub irq: bool,
+}
+
+impl Default for DMC {
+    fn default() -> Self {
+        DMC::new()
+    }
+}
+
+impl DMC {
+    pub fn new() -> Self {
+        DMC {
+            irq_enabled: false,
+            looping: false,
+            rate: RATES[0],
+            timer: RATES[0],
+            output: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            address: 0xC000,
+            bytes_left: 0,
+            buffer: None,
+            fetching: false,
+            shift: 0,
+            bits_left: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    // $4010-$4013.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4010 => {
+                self.irq_enabled = value & 0x80 != 0;
+                self.looping = value & 0x40 != 0;
+                self.rate = RATES[(value & 0x0F) as usize];
+                if !self.irq_enabled { self.irq = false; }
+            },
+            0x4011 => self.output = value & 0x7F,
+            0x4012 => self.sample_address = 0xC000 | (value as u16) << 6,
+            0x4013 => self.sample_length = (value as u16) << 4 | 1,
+            _ => ()
+        }
+    }
+
+    // $4015 bit 4 starts the sample (unless it is still playing) or stops it.
+    pub fn write_4015(&mut self, value: u8) {
+        self.irq = false;
+        if value & 0x10 == 0 {
+            self.bytes_left = 0;
+        } else if self.bytes_left == 0 {
+            self.restart();
+        }
+    }
+
+    // $4015 bits 4 (sample playing) and 7 (IRQ).
+    pub fn read_4015(&self) -> u8 {
+        (if self.bytes_left > 0 { 0x10 } else { 0 }) | (if self.irq { 0x80 } else { 0 })
+    }
+
+    fn restart(&mut self) {
+        self.address = self.sample_address;
+        self.bytes_left = self.sample_length;
+    }
+
+    // One CPU cycle. Returns the address of a byte to fetch when the buffer runs empty.
+    pub fn tick(&mut self) -> Option<u16> {
+        if self.timer > 1 {
+            self.timer -= 1;
+        } else {
+            self.timer = self.rate;
+            self.clock_output();
+        }
+        if self.buffer.is_none() && self.bytes_left > 0 && !self.fetching {
+            self.fetching = true;
+            return Some(self.address)
+        }
+        None
+    }
+
+    // Byte fetched by the DMA.
+    pub fn fill(&mut self, value: u8) {
+        self.fetching = false;
+        // Stopped through $4015 while the DMA was on its way.
+        if self.bytes_left == 0 { return }
+        self.buffer = Some(value);
+        self.address = self.address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_left -= 1;
+        if self.bytes_left == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    fn clock_output(&mut self) {
+        if !self.silence {
+            if self.shift & 0x01 != 0 {
+                if self.output <= 125 { self.output += 2; }
+            } else if self.output >= 2 {
+                self.output -= 2;
+            }
+        }
+        self.shift >>= 1;
+        self.bits_left -= 1;
+        if self.bits_left == 0 {
+            self.bits_left = 8;
+            match self.buffer.take() {
+                Some(value) => {
+                    self.shift = value;
+                    self.silence = false;
+                },
+                None => self.silence = true,
+            }
+        }
+    }
+
+    // Level through the APU's non-linear mixer (0.0 - ~0.57).
+    pub fn output(&self) -> f32 {
+        if self.output == 0 { return 0.0 }
+        159.79 / (22638.0 / self.output as f32 + 100.0)
+    }
+}
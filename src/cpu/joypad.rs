@@ -19,6 +19,12 @@ pub struct Joypad {
    pub button_status: JoypadButton,
 }
 
+impl Default for Joypad {
+    fn default() -> Self {
+        Joypad::new()
+    }
+}
+
 impl Joypad {
     pub fn new() -> Self {
         Joypad {
@@ -28,6 +34,12 @@ impl Joypad {
         }
     }
 
+    // Clears the shift register, the buttons are what the host holds.
+    pub fn power_on(&mut self) {
+        self.strobe = false;
+        self.button_index = 0;
+    }
+
     pub fn write(&mut self, data: u8) {
       self.strobe = data & 1 == 1;
       if self.strobe {
@@ -39,6 +51,10 @@ impl Joypad {
         self.button_status.toggle(JoypadButton::from_bits_truncate(value));
     }
 
+    pub fn set_buttons(&mut self, value: u8) {
+        self.button_status = JoypadButton::from_bits_truncate(value);
+    }
+
     pub fn read(&mut self) -> u8 {
         if self.button_index > 7 {
             return 1;
@@ -2,30 +2,87 @@ mod bus;
 mod instructions;
 mod cpu_status;
 mod joypad;
+mod dmc;
+mod vs_system;
 
 pub use self::bus::*;
-use crate::mapper::*;
-use crate::ppu::*;
 use cpu_status::*;
 use crate::cpu::instructions::*;
+use std::fmt;
 
-// CPU is guaranteed to receive NMI every interrupt
-const CYCLES_PER_FRAME: usize = 29780;
+// Where a JAM (KIL) opcode stopped the CPU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Halt {
+    pub pc: u16,
+    pub opcode: u8,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CPU halted at ${:04X} by opcode ${:02X}", self.pc, self.opcode)
+    }
+}
+
+// Register file, for debuggers and test harnesses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    pub p: u8,
+    pub pc: u16,
+}
 
-pub struct CPU {
+// What the 6502 sees of the system around it. The NES one is `BUS`, anything else
+// (NSF player, test harness) only needs memory and the interrupt lines.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+    // The rest of the system runs for one CPU cycle, after every access.
+    fn tick(&mut self);
+    // NMI edge, cleared once it is seen.
+    fn nmi(&mut self) -> bool;
+    // IRQ line, level triggered.
+    fn irq(&self) -> bool;
+    // RDY: asked before every read, the CPU stays halted on it while this returns true
+    // (the bus did the cycle's accesses itself). Used by DMA.
+    fn halt(&mut self, _addr: u16, _cycle: usize) -> bool { false }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU<B: Bus = BUS> {
     a: u8, // Accumulator
     y: u8, // register y
     x: u8, // register x
     pc: u16, // Program counter
     s: u8, // Stack pointer (256-byte stack at $0100-$01FF.)
     status: CPUStatus,
-    cycles_left: usize,
+    opcode: u8, // Instruction in progress
+    pub halted: Option<Halt>,
     cycles: usize,
-    pub bus: BUS,
+    overrun: usize, // Cycles `run_cycles` ran past its last target
+    // Interrupt polling, see `CPU::cycle`.
+    need_nmi: bool,
+    prev_need_nmi: bool,
+    run_irq: bool,
+    prev_run_irq: bool,
+    pub bus: B,
 }
 
 impl CPU {
-    pub fn new(rom: *const u8, mapper: Mapper_) -> Self {
+    // Runs until the PPU starts vblank. The instruction in progress is finished,
+    // its extra cycles are simply part of the next frame.
+    pub fn run_frame(&mut self) {
+        self.bus.ppu.frame_ready = false;
+        while !self.bus.ppu.frame_ready {
+            self.tick();
+        }
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    pub fn new(bus: B) -> Self {
         CPU {
             a: 0,
             x: 0,
@@ -33,163 +90,518 @@ impl CPU {
             pc: 0,
             s: 0xFD,
             status: CPUStatus::new(),
-            bus: BUS::new(rom, mapper, PPU::new()),
-            cycles_left: 0,
+            opcode: 0,
+            halted: None,
+            bus,
             cycles: 0,
+            overrun: 0,
+            need_nmi: false,
+            prev_need_nmi: false,
+            run_irq: false,
+            prev_run_irq: false,
         }
     }
 
-    pub fn run(&mut self) {
-        for _ in 0..CYCLES_PER_FRAME { 
+    pub fn get_registers(&self) -> Registers {
+        Registers { a: self.a, x: self.x, y: self.y, s: self.s, p: self.status.bits(), pc: self.pc }
+    }
+
+    // Execution continues from there, without a pending interrupt or JAM.
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.s = registers.s;
+        self.status.update(registers.p);
+        self.pc = registers.pc;
+        self.halted = None;
+        self.need_nmi = false;
+        self.prev_need_nmi = false;
+        self.run_irq = false;
+        self.prev_run_irq = false;
+    }
+
+    // Runs whole instructions for about `cycles` cycles, going over is taken back next time.
+    pub fn run_cycles(&mut self, cycles: usize) {
+        if self.overrun >= cycles {
+            self.overrun -= cycles;
+            return
+        }
+        let target = self.cycles + cycles - self.overrun;
+        while self.cycles < target {
             self.tick();
-            self.bus.tick(self.cycles_left);
         }
+        self.overrun = self.cycles - target;
+    }
+
+    // One instruction (or interrupt), returns the cycles it took.
+    pub fn step_instruction(&mut self) -> usize {
+        let cycles = self.cycles;
+        self.tick();
+        self.cycles - cycles
     }
 
     fn tick(&mut self) {
-        self.cycles_left = 0;
-        if let Some(Interrupt::Nmi) = self.bus.interrupt { 
-            self.nmi();
-            self.bus.interrupt = None;
+        // Only reset gets out of a JAM, the rest of the console keeps running.
+        if self.halted.is_some() {
+            self.read(0xFFFF);
+            return
+        }
+        // Polled at the end of the previous instruction's second to last cycle.
+        if self.prev_need_nmi || self.prev_run_irq {
+            self.interrupt();
         } else {
-            let op = self.bus.read(self.pc);
-            self.pc += 1;
-            let (fun, addr_mode) = &CPU::OPCODES[op as usize];
+            let op = self.read(self.pc);
+            self.opcode = op;
+            self.pc = self.pc.wrapping_add(1);
+            let (fun, addr_mode) = &Self::OPCODES[op as usize];
             let addr = self.get_address_mode(addr_mode.clone()); 
             fun(self, addr);
-            if self.bus.suspend {
-                if self.cycles & 1 == 0 { 
-                    self.cycles_left += 513; 
-                } else { 
-                    self.cycles_left += 514; 
-                }
-                self.bus.suspend = false;
-            }
         }
-        self.cycles += self.cycles_left;
     }
 
-    pub fn reset(&mut self) {
-        self.cycles_left = 7;
+    // Cold boot: registers cleared, then the reset sequence (which takes S from 0 to $FD).
+    // The bus is powered on separately.
+    pub fn power_on(&mut self) {
         self.x = 0;
         self.y = 0;
         self.a = 0;
-        self.s = 0xFD;
+        self.s = 0;
         self.status = CPUStatus::new();
+        self.start();
+    }
+
+    // Reset button: only I is set and S goes down by 3, RAM and the other registers are kept.
+    pub fn reset(&mut self) {
+        self.status.set_interrupt(true);
+        self.start();
+    }
+
+    fn start(&mut self) {
+        self.halted = None;
+        self.need_nmi = false;
+        self.prev_need_nmi = false;
+        self.run_irq = false;
+        self.prev_run_irq = false;
+        // Same sequence as an interrupt, with the stack writes turned into reads.
+        self.read(self.pc);
+        self.read(self.pc);
+        for _ in 0..3 {
+            self.read(0x0100 | self.s as u16);
+            self.s = self.s.wrapping_sub(1);
+        }
         self.pc = self.read_address(RESET_VECTOR);
     }
 
-    fn nmi(&mut self) {
-        self.cycles_left = 7; 
+    // NMI and IRQ share the sequence (a BRK with the opcode fetch discarded), the vector
+    // is only picked after PC is pushed. An NMI by then hijacks the IRQ, B stays clear.
+    fn interrupt(&mut self) {
+        self.read(self.pc);
+        self.read(self.pc);
         self.push_stack(((self.pc & 0xFF00) >> 8) as u8);
         self.push_stack((self.pc & 0x00FF) as u8);
-        self.push_stack(self.status.bits() & !0x10);
+        let vector = self.interrupt_vector(IRQ_VECTOR);
+        self.push_stack((self.status.bits() & !0x10) | 0x20);
         self.status.set_interrupt(true);
-        self.pc = self.read_address(NMI_VECTOR);
+        self.pc = self.read_address(vector);
+        // Like BRK, the first instruction of the handler runs before the next interrupt.
+        self.prev_need_nmi = false;
+        self.prev_run_irq = false;
+    }
+
+    // IRQ/BRK vector, unless an NMI is pending (which is then serviced).
+    fn interrupt_vector(&mut self, vector: u16) -> u16 {
+        if self.need_nmi {
+            self.need_nmi = false;
+            NMI_VECTOR
+        } else {
+            vector
+        }
     }
 
+    // One CPU cycle of the rest of the system. The 6502 then latches an NMI edge
+    // and polls the IRQ line, both only visible a cycle later.
+    fn cycle(&mut self) {
+        self.bus.tick();
+        self.cycles += 1;
+        self.prev_need_nmi = self.need_nmi;
+        if self.bus.nmi() { self.need_nmi = true; }
+        self.prev_run_irq = self.run_irq;
+        self.run_irq = self.bus.irq() && !self.status.interrupt();
+    }
+
+    // Every bus access takes a cycle, dummy ones included.
+    fn read(&mut self, addr: u16) -> u8 {
+        while self.bus.halt(addr, self.cycles) { self.cycle(); }
+        let value = self.bus.read(addr);
+        self.cycle();
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.bus.write(addr, value);
+        self.cycle();
+    }
+
+    // Fetches the operand, with the same (dummy) reads the 6502 does. The operand itself
+    // is left to the instruction, except for indexed reads that don't cross a page:
+    // for those the dummy read is the real one.
+    // https://www.nesdev.org/6502_cpu.txt
     fn get_address_mode(&mut self, addr_mode: AddrMode) -> u16 {
         match addr_mode {
-            AddrMode::Rel(cycles) => { self.cycles_left += cycles & CYCLE_MASK; 0 },
-            AddrMode::Acc(cycles) => { self.cycles_left += cycles & CYCLE_MASK; 0 },
-            AddrMode::Impl(cycles) => { self.cycles_left += cycles & CYCLE_MASK; 0 },
-            AddrMode::Imm(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
+            AddrMode::Rel(_) => 0,
+            AddrMode::Acc(_) | AddrMode::Impl(_) => { self.read(self.pc); 0 },
+            AddrMode::Imm(_) => {
                 let operand = self.pc;
-                self.pc += 1;
+                self.pc = self.pc.wrapping_add(1);
                 operand
             }
-            AddrMode::Ind(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
+            AddrMode::Ind(_) => {
                 let addr = self.read_address(self.pc);
-                self.pc += 2;
-                let operand = (self.bus.read((addr & 0xFF00) | ((addr + 1) & 0x00FF)) as u16) * 0x100 | self.bus.read(addr) as u16;
-                operand
+                self.pc = self.pc.wrapping_add(2);
+                // The high byte doesn't carry into the next page.
+                let low = self.read(addr) as u16;
+                let high = self.read((addr & 0xFF00) | (addr.wrapping_add(1) & 0x00FF)) as u16;
+                high << 8 | low
             }
-            AddrMode::Abs(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
+            AddrMode::Abs(_) => {
                 let operand = self.read_address(self.pc);
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
                 operand
             }
-            AddrMode::Zp(cycles) => { 
-                self.cycles_left += cycles & CYCLE_MASK;
-                let operand = self.bus.read(self.pc) as u16;
-                self.pc += 1;
+            AddrMode::Zp(_) => { 
+                let operand = self.read(self.pc) as u16;
+                self.pc = self.pc.wrapping_add(1);
                 operand
             }
-            AddrMode::ZpX(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
-                let operand = (self.bus.read(self.pc) + self.x) as u16; 
-                self.pc += 1;
-                operand
+            AddrMode::ZpX(_) => {
+                let addr = self.read(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                self.read(addr as u16);
+                addr.wrapping_add(self.x) as u16
             }
-            AddrMode::ZpY(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
-                let operand = (self.bus.read(self.pc) + self.y) as u16;
-                self.pc += 1;
-                operand
+            AddrMode::ZpY(_) => {
+                let addr = self.read(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                self.read(addr as u16);
+                addr.wrapping_add(self.y) as u16
             }
             AddrMode::AbsX(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
                 let addr = self.read_address(self.pc);
-                self.pc += 2;
-                let operand = addr + self.x as u16;
-                if (cycles as u8 & CYCLE_PAGE_CROSS_MASK) == 0 {
-                    self.set_page_crossed(addr, operand);
-                }
-                operand
+                self.pc = self.pc.wrapping_add(2);
+                self.index(addr, self.x, cycles)
             }
             AddrMode::AbsY(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
                 let addr = self.read_address(self.pc);
-                self.pc += 2;
-                let operand = addr + self.y as u16;
-                if (cycles as u8 & CYCLE_PAGE_CROSS_MASK) == 0 {
-                    self.set_page_crossed(addr, operand);
-                }
-                operand
+                self.pc = self.pc.wrapping_add(2);
+                self.index(addr, self.y, cycles)
             }
-            AddrMode::IndX(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
-                let arg = (self.bus.read(self.pc) + self.x) as u16;
-                self.pc += 1;
-                self.bus.read(arg & 0xFF) as u16 | (self.bus.read((arg + 1) & 0xFF) as u16) * 0x100
+            AddrMode::IndX(_) => {
+                let arg = self.read(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                self.read(arg as u16);
+                let arg = arg.wrapping_add(self.x);
+                self.read(arg as u16) as u16 | (self.read(arg.wrapping_add(1) as u16) as u16) << 8
             }
             AddrMode::IndrY(cycles) => {
-                self.cycles_left += cycles & CYCLE_MASK;
-                let arg = self.bus.read(self.pc) as u16;
-                self.pc += 1;
-                let addr = self.bus.read(arg) as u16 | (self.bus.read((arg + 1) & 0xFF) as u16) * 0x100;
-                let operand = addr + self.y as u16;
-                if (cycles as u8 & CYCLE_PAGE_CROSS_MASK) == 0 {
-                    self.set_page_crossed(addr, operand);
-                }
-                operand
+                let arg = self.read(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                let addr = self.read(arg as u16) as u16 | (self.read(arg.wrapping_add(1) as u16) as u16) << 8;
+                self.index(addr, self.y, cycles)
             }
             AddrMode::None => 0
         }
     }
 
-    fn set_page_crossed(&mut self, a: u16, b: u16) {
-        if (a & 0xFF00) != (b & 0xFF00) { 
-            self.cycles_left += 1; 
+    // The low byte is added first, the 6502 reads from that (possibly wrong page) address
+    // while fixing the high byte. Writes and read-modify-writes always take that cycle.
+    fn index(&mut self, addr: u16, index: u8, cycles: usize) -> u16 {
+        let operand = addr.wrapping_add(index as u16);
+        if (addr & 0xFF00) != (operand & 0xFF00) || (cycles as u8 & CYCLE_PAGE_CROSS_MASK) != 0 {
+            self.read((addr & 0xFF00) | (operand & 0x00FF));
         }
+        operand
     }
 
     fn push_stack(&mut self, val: u8) {
-        self.bus.write(0x100 + self.s as u16, val);
-        self.s -= 1;
+        self.write(0x100 + self.s as u16, val);
+        self.s = self.s.wrapping_sub(1);
     }
     
     fn pull_stack(&mut self) -> u8 {
-        self.s += 1;
-        let v = self.bus.read(0x100 + self.s as u16);
-        v
+        self.s = self.s.wrapping_add(1);
+        self.read(0x100 + self.s as u16)
     }
 
     fn read_address(&mut self, addr: u16) -> u16 {
-        let addr = (self.bus.read(addr + 1) as u16) * 0x100 + self.bus.read(addr) as u16;
-        addr
+        let low = self.read(addr) as u16;
+        let high = self.read(addr.wrapping_add(1)) as u16;
+        high << 8 | low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ mapper::new, ppu::PPU };
+
+    // NROM running `program` from $8000, the rest of PRG is NOPs.
+    fn cpu(program: &[(u16, &[u8])]) -> Box<CPU> {
+        let mut rom = vec![b'N', b'E', b'S', 0x1A, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.resize(16 + 0x8000 + 0x2000, 0xEA);
+        for &(addr, bytes) in program {
+            let start = 16 + addr as usize - 0x8000;
+            rom[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+        rom[16 + 0x7FFC..16 + 0x7FFE].copy_from_slice(&[0x00, 0x80]);
+        let (mapper, ..) = new(&rom, &[], "").unwrap();
+        let mut cpu = Box::new(CPU::new(BUS::new(mapper, PPU::new())));
+        cpu.reset();
+        cpu
+    }
+
+    // Cycles taken by each of the next `count` instructions.
+    fn cycles(cpu: &mut CPU, count: usize) -> Vec<usize> {
+        (0..count).map(|_| {
+            let start = cpu.cycles;
+            cpu.tick();
+            cpu.cycles - start
+        }).collect()
+    }
+
+    #[test]
+    fn instruction_cycles() {
+        let mut cpu = cpu(&[(0x8000, &[
+            0xA2, 0x01,       // LDX #$01
+            0xBD, 0xFF, 0x80, // LDA $80FF,X (page crossed)
+            0xBD, 0x00, 0x80, // LDA $8000,X
+            0x9D, 0x00, 0x02, // STA $0200,X
+            0xFE, 0x00, 0x02, // INC $0200,X
+            0x20, 0x20, 0x80, // JSR $8020
+            0x18,             // CLC
+            0x90, 0x00,       // BCC +0
+        ]), (0x8020, &[0x60])]);
+        assert_eq!(cycles(&mut cpu, 9), [2, 5, 4, 5, 7, 6, 6, 2, 3]);
+        assert_eq!(cpu.pc, 0x8014);
+    }
+
+    #[test]
+    fn run_cycles_overrun() {
+        // NOPs (2 cycles), then INC $0200,X (7 cycles).
+        let mut cpu = cpu(&[(0x8005, &[0xFE, 0x00, 0x02])]);
+        let start = cpu.cycles;
+        // 3 NOPs for 5 cycles, the extra one comes off the next call.
+        cpu.run_cycles(5);
+        assert_eq!((cpu.cycles - start, cpu.overrun), (6, 1));
+        cpu.run_cycles(5);
+        assert_eq!((cpu.cycles - start, cpu.overrun), (10, 0));
+        // The INC runs 4 cycles over, which covers all of the next call.
+        cpu.run_cycles(3);
+        assert_eq!((cpu.cycles - start, cpu.overrun), (17, 4));
+        cpu.run_cycles(3);
+        assert_eq!((cpu.cycles - start, cpu.overrun), (17, 1));
+        cpu.run_cycles(5);
+        assert_eq!((cpu.cycles - start, cpu.overrun), (21, 0));
+    }
+
+    // The read before the page fix-up goes to $4016 and shifts the joypad.
+    #[test]
+    fn dummy_reads() {
+        let mut cpu = cpu(&[(0x8000, &[
+            0xA9, 0x01, 0x8D, 0x16, 0x40, // Strobe
+            0xA9, 0x00, 0x8D, 0x16, 0x40,
+            0xA2, 0x17,                   // LDX #$17
+            0xBD, 0xFF, 0x40,             // LDA $40FF,X
+            0xAD, 0x16, 0x40,             // LDA $4016
+        ])]);
+        cpu.bus.joypads[0].set_buttons(0x02);
+        cycles(&mut cpu, 7);
+        assert_eq!(cpu.a & 1, 1);
+    }
+
+    // Runs `program` to its end.
+    fn run(program: &[u8]) -> Box<CPU> {
+        let mut cpu = cpu(&[(0x8000, program)]);
+        while cpu.pc < 0x8000 + program.len() as u16 { cpu.tick(); }
+        cpu
+    }
+
+    // N, V, Z and C.
+    fn flags(cpu: &CPU) -> [bool; 4] {
+        [cpu.status.negative(), cpu.status.overflow(), cpu.status.zero(), cpu.status.carry()]
+    }
+
+    #[test]
+    fn anc_arr_sbx() {
+        // LDA #$FF, CLC, ANC #$80
+        let cpu = run(&[0xA9, 0xFF, 0x18, 0x0B, 0x80]);
+        assert_eq!((cpu.a, flags(&cpu)), (0x80, [true, false, false, true]));
+        // LDA #$FF, SEC, ARR #$C0
+        let cpu = run(&[0xA9, 0xFF, 0x38, 0x6B, 0xC0]);
+        assert_eq!((cpu.a, flags(&cpu)), (0xE0, [true, false, false, true]));
+        // LDA #$FF, CLC, ARR #$40
+        let cpu = run(&[0xA9, 0xFF, 0x18, 0x6B, 0x40]);
+        assert_eq!((cpu.a, flags(&cpu)), (0x20, [false, true, false, false]));
+        // LDA #$F0, LDX #$3C, SBX #$10
+        let cpu = run(&[0xA9, 0xF0, 0xA2, 0x3C, 0xCB, 0x10]);
+        assert_eq!((cpu.x, flags(&cpu)), (0x20, [false, false, false, true]));
+        // LDA #$F0, LDX #$3C, SBX #$40
+        let cpu = run(&[0xA9, 0xF0, 0xA2, 0x3C, 0xCB, 0x40]);
+        assert_eq!((cpu.x, flags(&cpu)), (0xF0, [true, false, false, false]));
+    }
+
+    // With the magic constant $EE.
+    #[test]
+    fn ane_lxa() {
+        // LDA #$00, LDX #$0F, ANE #$FF
+        let cpu = run(&[0xA9, 0x00, 0xA2, 0x0F, 0x8B, 0xFF]);
+        assert_eq!(cpu.a, 0x0E);
+        // LDA #$00, LXA #$FF
+        let cpu = run(&[0xA9, 0x00, 0xAB, 0xFF]);
+        assert_eq!((cpu.a, cpu.x), (0xEE, 0xEE));
+    }
+
+    // Stored value is ANDed with the high byte + 1, which becomes the high byte on a page cross.
+    #[test]
+    fn store_high() {
+        // LDX #$FF, LDY #$01, SHX $0200,Y
+        let mut cpu = run(&[0xA2, 0xFF, 0xA0, 0x01, 0x9E, 0x00, 0x02]);
+        assert_eq!(cpu.bus.read(0x0201), 0x03);
+        // LDX #$01, LDY #$01, SHX $02FF,Y
+        let mut cpu = run(&[0xA2, 0x01, 0xA0, 0x01, 0x9E, 0xFF, 0x02]);
+        assert_eq!((cpu.bus.read(0x0100), cpu.bus.read(0x0300)), (0x01, 0x00));
+        // LDY #$FF, LDX #$01, SHY $0200,X
+        let mut cpu = run(&[0xA0, 0xFF, 0xA2, 0x01, 0x9C, 0x00, 0x02]);
+        assert_eq!(cpu.bus.read(0x0201), 0x03);
+        // LDA #$FF, LDX #$F7, LDY #$00, SHA $0600,Y
+        let mut cpu = run(&[0xA9, 0xFF, 0xA2, 0xF7, 0xA0, 0x00, 0x9F, 0x00, 0x06]);
+        assert_eq!(cpu.bus.read(0x0600), 0x07);
+        // LDA #$FF, LDX #$F7, LDY #$00, TAS $0600,Y
+        let mut cpu = run(&[0xA9, 0xFF, 0xA2, 0xF7, 0xA0, 0x00, 0x9B, 0x00, 0x06]);
+        assert_eq!((cpu.s, cpu.bus.read(0x0600)), (0xF7, 0x07));
+    }
+
+    // $6F is RRA absolute.
+    #[test]
+    fn rra_absolute() {
+        // LDA #$02, STA $0210, LDA #$01, CLC, RRA $0210
+        let mut cpu = run(&[0xA9, 0x02, 0x8D, 0x10, 0x02, 0xA9, 0x01, 0x18, 0x6F, 0x10, 0x02]);
+        assert_eq!((cpu.a, cpu.bus.read(0x0210)), (0x02, 0x01));
+    }
+
+    #[test]
+    fn jam_halts_until_reset() {
+        // LDA #$01, JAM, LDA #$02
+        let mut cpu = cpu(&[(0x8000, &[0xA9, 0x01, 0x02, 0xA9, 0x02])]);
+        assert_eq!(cycles(&mut cpu, 2), [2, 2]);
+        assert_eq!(cpu.halted, Some(Halt { pc: 0x8002, opcode: 0x02 }));
+        // One read per step, the rest of the console keeps running.
+        assert_eq!(cycles(&mut cpu, 3), [1, 1, 1]);
+        assert_eq!((cpu.a, cpu.pc), (0x01, 0x8003));
+        cpu.reset();
+        assert_eq!((cpu.halted, cpu.pc), (None, 0x8000));
+    }
+
+    // Reads of anything that doesn't drive the data bus return the last value on it,
+    // the high byte of the address here. PPU registers return the PPU's own latch.
+    #[test]
+    fn open_bus() {
+        let mut cpu = cpu(&[(0x8000, &[
+            0xAD, 0x00, 0x40,             // LDA $4000
+            0xAD, 0x00, 0x50,             // LDA $5000
+            0xAD, 0x16, 0x40,             // LDA $4016
+            0xA9, 0x5A, 0x8D, 0x03, 0x20, // LDA #$5A, STA $2003
+            0xAD, 0x05, 0x20,             // LDA $2005
+        ])]);
+        let a: Vec<u8> = (0..6).map(|_| { cpu.tick(); cpu.a }).collect();
+        assert_eq!(a, [0x40, 0x50, 0x40, 0x5A, 0x5A, 0x5A]);
+    }
+
+    // 64KB of RAM, every access is logged. The NMI edge comes with the IRQ vector fetch.
+    struct TestBus {
+        ram: Vec<u8>,
+        accesses: Vec<(u16, u8, bool)>, // Address, value, write
+        irq: bool,
+        nmi: bool,
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.accesses.push((addr, self.ram[addr as usize], false));
+            if addr == IRQ_VECTOR { self.nmi = true; }
+            self.ram[addr as usize]
+        }
+        fn write(&mut self, addr: u16, value: u8) {
+            self.accesses.push((addr, value, true));
+            self.ram[addr as usize] = value;
+        }
+        fn tick(&mut self) {}
+        fn nmi(&mut self) -> bool { std::mem::take(&mut self.nmi) }
+        fn irq(&self) -> bool { self.irq }
+    }
+
+    #[test]
+    fn any_bus() {
+        let mut ram = vec![0; 0x10000];
+        ram[0x8000..0x8008].copy_from_slice(&[
+            0xA9, 0x12,       // LDA #$12
+            0x8D, 0x00, 0x03, // STA $0300
+            0xEE, 0x00, 0x03, // INC $0300
+        ]);
+        ram[RESET_VECTOR as usize..RESET_VECTOR as usize + 2].copy_from_slice(&[0x00, 0x80]);
+        let mut cpu = CPU::new(TestBus { ram, accesses: Vec::new(), irq: false, nmi: false });
+        cpu.power_on();
+        cpu.bus.accesses.clear();
+        let cycles: Vec<usize> = (0..3).map(|_| cpu.step_instruction()).collect();
+        assert_eq!(cycles, [2, 4, 6]);
+        assert_eq!(cpu.bus.accesses, [
+            (0x8000, 0xA9, false), (0x8001, 0x12, false),
+            (0x8002, 0x8D, false), (0x8003, 0x00, false), (0x8004, 0x03, false), (0x0300, 0x12, true),
+            (0x8005, 0xEE, false), (0x8006, 0x00, false), (0x8007, 0x03, false),
+            (0x0300, 0x12, false), (0x0300, 0x12, true), (0x0300, 0x13, true),
+        ]);
+    }
+
+    #[test]
+    fn nmi_during_interrupt_waits_for_the_handler() {
+        // NOPs everywhere, IRQ handler at $9000, NMI handler at $A000.
+        let mut ram = vec![0xEA; 0x10000];
+        ram[NMI_VECTOR as usize..NMI_VECTOR as usize + 2].copy_from_slice(&[0x00, 0xA0]);
+        ram[IRQ_VECTOR as usize..IRQ_VECTOR as usize + 2].copy_from_slice(&[0x00, 0x90]);
+        let mut cpu = CPU::new(TestBus { ram, accesses: Vec::new(), irq: true, nmi: false });
+        cpu.set_registers(Registers { a: 0, x: 0, y: 0, s: 0xFD, p: 0x00, pc: 0x8000 });
+
+        cpu.step_instruction();
+        assert_eq!(cpu.step_instruction(), 7);
+        assert_eq!(cpu.get_registers().pc, 0x9000);
+        // The handler's first instruction, then the NMI.
+        cpu.step_instruction();
+        assert_eq!(cpu.get_registers().pc, 0x9001);
+        cpu.step_instruction();
+        assert_eq!(cpu.get_registers().pc, 0xA000);
+    }
+
+    #[test]
+    fn irq_during_brk_waits_for_the_handler() {
+        // CLI, BRK with the IRQ line held, IRQ handler at $9000, NMI handler at $A000.
+        let mut ram = vec![0xEA; 0x10000];
+        ram[0x8000..0x8003].copy_from_slice(&[0x58, 0x00, 0x00]);
+        ram[NMI_VECTOR as usize..NMI_VECTOR as usize + 2].copy_from_slice(&[0x00, 0xA0]);
+        ram[IRQ_VECTOR as usize..IRQ_VECTOR as usize + 2].copy_from_slice(&[0x00, 0x90]);
+        let mut cpu = CPU::new(TestBus { ram, accesses: Vec::new(), irq: true, nmi: false });
+        cpu.set_registers(Registers { a: 0, x: 0, y: 0, s: 0xFD, p: 0x04, pc: 0x8000 });
+
+        // CLI takes effect after the next instruction, so BRK runs and the IRQ is seen during it.
+        cpu.step_instruction();
+        assert_eq!(cpu.step_instruction(), 7);
+        assert_eq!(cpu.get_registers().pc, 0x9000);
+        assert_eq!(cpu.bus.ram[0x01FB] & 0x14, 0x10);
+        // The handler's first instruction, then only the NMI (I is set).
+        cpu.step_instruction();
+        assert_eq!(cpu.get_registers().pc, 0x9001);
+        cpu.step_instruction();
+        assert_eq!(cpu.get_registers().pc, 0xA000);
+        cpu.step_instruction();
+        assert_eq!(cpu.get_registers().pc, 0xA001);
     }
 }
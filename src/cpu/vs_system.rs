@@ -0,0 +1,46 @@
+// VS. System cabinet inputs, read alongside the controllers.
+// https://www.nesdev.org/wiki/Vs._System
+pub const COIN_1: u8 = 0b001;
+pub const COIN_2: u8 = 0b010;
+pub const SERVICE: u8 = 0b100;
+
+pub struct VsSystem {
+    pub dip_switches: u8, // Switch 1 in bit 0
+    pub coins: u8, // COIN_1, COIN_2 and SERVICE, held by the host
+    pub coin_counter: bool,
+}
+
+impl Default for VsSystem {
+    fn default() -> Self {
+        VsSystem::new()
+    }
+}
+
+impl VsSystem {
+    pub fn new() -> Self {
+        VsSystem {
+            dip_switches: 0,
+            coins: 0,
+            coin_counter: false,
+        }
+    }
+
+    // $4016: service button in bit 2, DIP switches 1-2 in bits 3-4, coins in bits 5-6.
+    pub fn read_4016(&self) -> u8 {
+        let mut value = (self.dip_switches & 0x03) << 3;
+        if self.coins & SERVICE != 0 { value |= 0x04; }
+        if self.coins & COIN_1 != 0 { value |= 0x20; }
+        if self.coins & COIN_2 != 0 { value |= 0x40; }
+        value
+    }
+
+    // $4017: DIP switches 3-8 in bits 2-7.
+    pub fn read_4017(&self) -> u8 {
+        self.dip_switches & 0xFC
+    }
+
+    // $4020: coin counter, pulsed by the game for every credit.
+    pub fn write_4020(&mut self, value: u8) {
+        self.coin_counter = value & 0x01 != 0;
+    }
+}
@@ -1,8 +1,23 @@
-use crate::{ cpu::*, mapper::*, ppu::COLORS };
+use crate::{ cpu::*, mapper::*, ppu::PPU };
+#[cfg(not(target_arch = "wasm32"))]
+use std::{ fs, io, path::Path };
 
+// Before a game is loaded getters return empty values (0, null, None) and the rest does nothing.
 pub struct Emulator {
     cpu: Option<CPU>,
     rom: Vec<u8>,
+    bios: Vec<u8>,
+    database: Vec<u8>, // Extra game database entries (text)
+    disk_image: Vec<u8>,
+    debug_state: String,
+    corrections: Corrections,
+    ram_init: RamInit,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Emulator::new()
+    }
 }
 
 impl Emulator {
@@ -10,57 +25,548 @@ impl Emulator {
         Emulator { 
             cpu: None,
             rom: Vec::new(),
+            bios: Vec::new(),
+            database: Vec::new(),
+            disk_image: Vec::new(),
+            debug_state: String::new(),
+            corrections: Corrections::empty(),
+            ram_init: RamInit::Zeros,
+        }
+    }
+
+    // Loads the ROM written through `get_rom_pointer` and powers the console on.
+    // On error the previous game (if any) is kept.
+    pub fn disassemble(&mut self) -> Result<(), LoadError> {
+        let (mapper, console, corrections) = new(&self.rom, &self.bios, &String::from_utf8_lossy(&self.database))?;
+        self.corrections = corrections;
+        let mut cpu = CPU::new(BUS::new(mapper, PPU::new()));
+        cpu.bus.set_console(console);
+        self.cpu = Some(cpu);
+        self.boot();
+        Ok(())
+    }
+
+    pub fn load_rom(&mut self, bytes: &[u8]) -> Result<(), LoadError> {
+        self.rom = bytes.to_vec();
+        self.disassemble()
+    }
+
+    // Header fields overridden by the game database (see mapper::Corrections).
+    pub fn get_corrections(&self) -> u16 {
+        self.corrections.bits()
+    }
+
+    // Color of palette RAM entry `index` (0-31), None before a game is loaded.
+    pub fn get_color(&self, index: usize) -> Option<u32> {
+        let cpu = self.cpu.as_ref()?;
+        let color = *cpu.bus.ppu.palette_table.get(index)?;
+        Some(cpu.bus.ppu.model.colors()[color as usize & 0x3F])
+    }
+
+    pub fn set_len(&mut self, value: usize) {
+        self.rom.clear();
+        self.rom.resize(value, 0);
+    }
+
+    pub fn get_rom_pointer(&mut self) -> *mut u8 {
+        self.rom.as_mut_ptr()
+    }
+
+    pub fn set_bios_len(&mut self, value: usize) {
+        self.bios.resize(value, 0);
+    }
+
+    pub fn get_bios_pointer(&mut self) -> *mut u8 {
+        self.bios.as_mut_ptr()
+    }
+
+    // FDS BIOS (8KB) used by the next disk image load.
+    pub fn set_bios(&mut self, bytes: &[u8]) {
+        self.bios = bytes.to_vec();
+    }
+
+    // Game database entries used on top of the built-in ones by the next load, one per
+    // line in the database.txt format.
+    pub fn set_database(&mut self, text: &str) {
+        self.database = text.as_bytes().to_vec();
+    }
+
+    pub fn set_database_len(&mut self, value: usize) {
+        self.database.clear();
+        self.database.resize(value, 0);
+    }
+
+    pub fn get_database_pointer(&mut self) -> *mut u8 {
+        self.database.as_mut_ptr()
+    }
+
+    // Number of disk sides, 0 if the loaded game isn't an FDS disk.
+    pub fn get_disk_sides(&mut self) -> usize {
+        match self.cpu.as_mut() {
+            Some(cpu) => cpu.bus.mapper.as_fds().map_or(0, |fds| fds.get_disk_sides()),
+            None => 0
+        }
+    }
+
+    // Inserted disk side, -1 if the drive is empty.
+    pub fn get_disk_side(&mut self) -> isize {
+        match self.cpu.as_mut() {
+            Some(cpu) => cpu.bus.mapper.as_fds().and_then(|fds| fds.get_disk_side()).map_or(-1, |side| side as isize),
+            None => -1
+        }
+    }
+
+    pub fn insert_disk(&mut self, side: usize) {
+        if let Some(fds) = self.cpu.as_mut().and_then(|cpu| cpu.bus.mapper.as_fds()) {
+            fds.insert_disk(side);
+        }
+    }
+
+    pub fn eject_disk(&mut self) {
+        if let Some(fds) = self.cpu.as_mut().and_then(|cpu| cpu.bus.mapper.as_fds()) {
+            fds.eject_disk();
         }
     }
 
-    pub fn disassemble(&mut self) {
-        let mapper = match new(&self.rom) { 
-            Ok(m) => m, 
-            Err(str) => { panic!("{str}"); }
+    // Rebuilds the disk image (with the game's writes) and returns its length, 0 without a disk
+    // or when the written files no longer fit the image format.
+    pub fn save_disk(&mut self) -> usize {
+        self.disk_image = match self.cpu.as_mut() {
+            Some(cpu) => cpu.bus.mapper.as_fds().and_then(|fds| fds.get_disk_image().ok()).unwrap_or_default(),
+            None => Vec::new()
+        };
+        self.disk_image.len()
+    }
+
+    pub fn get_disk_image_pointer(&self) -> *const u8 {
+        self.disk_image.as_ptr()
+    }
+
+    // Mapper state as JSON, for the memory map viewer.
+    pub fn get_debug_state(&mut self) -> usize {
+        self.debug_state = match self.cpu.as_ref() {
+            Some(cpu) => cpu.bus.mapper.get_debug_state().to_json(),
+            None => String::new()
         };
-        self.cpu = Some(CPU::new(self.rom.as_ptr(), mapper));
+        self.debug_state.len()
     }
 
-    pub fn get_color(&self, index: usize) -> u32 {
+    pub fn get_debug_state_pointer(&self) -> *const u8 {
+        self.debug_state.as_ptr()
+    }
+
+    pub fn has_battery(&self) -> bool {
         match self.cpu.as_ref() {
-            Some(cpu) => COLORS[cpu.bus.ppu.palette_table[index] as usize],
-            None => { panic!("Emulator not initialized."); }
+            Some(cpu) => cpu.bus.mapper.has_battery(),
+            None => false
         }
     }
 
-    pub fn set_len(&mut self, value: usize) {
-        unsafe { self.rom.set_len(value); }
+    // Save RAM can be written through this pointer once the game is loaded, power on keeps it.
+    pub fn get_save_ram_pointer(&mut self) -> *mut u8 {
+        match self.cpu.as_mut() {
+            Some(cpu) => cpu.bus.mapper.get_save_ram().map_or(std::ptr::null_mut(), |ram| ram.as_mut_ptr()),
+            None => std::ptr::null_mut()
+        }
     }
 
-    pub fn get_rom_pointer(&self) -> *const u8 {
-        self.rom.as_ptr()
+    pub fn get_save_ram_length(&mut self) -> usize {
+        match self.cpu.as_mut() {
+            Some(cpu) => cpu.bus.mapper.get_save_ram().map_or(0, |ram| ram.len()),
+            None => 0
+        }
+    }
+
+    // Loads a .sav file into save RAM, a missing file leaves it untouched. A file of another
+    // size fills what overlaps.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_save(&mut self, path: &Path) -> io::Result<()> {
+        let Some(ram) = self.cpu.as_mut().and_then(|cpu| cpu.bus.mapper.get_save_ram()) else { return Ok(()) };
+        match fs::read(path) {
+            Ok(save) => {
+                let len = save.len().min(ram.len());
+                ram[..len].copy_from_slice(&save[..len]);
+                Ok(())
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e)
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush_save(&mut self, path: &Path) -> io::Result<()> {
+        match self.cpu.as_mut().and_then(|cpu| cpu.bus.mapper.get_save_ram()) {
+            Some(ram) => fs::write(path, ram),
+            None => Ok(())
+        }
+    }
+
+    // Samples (mono, 44.1kHz) produced by the last `run_frame`, `run_cycles` or `step_instruction`.
+    pub fn audio(&self) -> &[f32] {
+        match self.cpu.as_ref() {
+            Some(cpu) => &cpu.bus.audio,
+            None => &[]
+        }
+    }
+
+    pub fn get_audio_pointer(&self) -> *const f32 {
+        match self.cpu.as_ref() {
+            Some(cpu) => cpu.bus.audio.as_ptr(),
+            None => std::ptr::null()
+        }
+    }
+
+    pub fn get_audio_length(&self) -> usize {
+        match self.cpu.as_ref() {
+            Some(cpu) => cpu.bus.audio.len(),
+            None => 0
+        }
     }
 
     pub fn toggle_button(&mut self, value: u8) {
-        match self.cpu.as_mut() {
-            Some(cpu) => cpu.bus.joypad.set_button(value),
-            None => { panic!("Emulator not initialized."); }
+        if let Some(cpu) = self.cpu.as_mut() {
+            cpu.bus.joypads[0].set_button(value);
         }
     }
 
-    pub fn reset(&mut self) {
-        match self.cpu.as_mut() {
-            Some(cpu) => cpu.reset(),
-            None => { panic!("Emulator not initialized."); }
+    // Pressed buttons of controller `port` (0 or 1), see `JoypadButton`.
+    pub fn set_buttons(&mut self, port: usize, mask: u8) {
+        if let Some(joypad) = self.cpu.as_mut().and_then(|cpu| cpu.bus.joypads.get_mut(port)) {
+            joypad.set_buttons(mask);
+        }
+    }
+
+    pub fn is_vs_system(&self) -> bool {
+        match self.cpu.as_ref() {
+            Some(cpu) => cpu.bus.vs_system.is_some(),
+            None => false
+        }
+    }
+
+    pub fn set_dip_switches(&mut self, value: u8) {
+        if let Some(vs_system) = self.cpu.as_mut().and_then(|cpu| cpu.bus.vs_system.as_mut()) {
+            vs_system.dip_switches = value;
+        }
+    }
+
+    // Coin slots and service button (see cpu::COIN_1, COIN_2 and SERVICE), toggled like buttons.
+    pub fn toggle_coin(&mut self, value: u8) {
+        if let Some(vs_system) = self.cpu.as_mut().and_then(|cpu| cpu.bus.vs_system.as_mut()) {
+            vs_system.coins ^= value;
+        }
+    }
+
+    // Set when the game crashed on a JAM opcode, until the next reset.
+    pub fn get_halt(&self) -> Option<Halt> {
+        self.cpu.as_ref().and_then(|cpu| cpu.halted)
+    }
+
+    // Used by the next `power_on`.
+    pub fn set_ram_init(&mut self, ram_init: RamInit) {
+        self.ram_init = ram_init;
+    }
+
+    // Cold boot, `disassemble` already does one. The mapper is built again from the ROM,
+    // only battery RAM and the disk (its contents and the side in the drive) survive.
+    // If that fails (a disk too full to save, a database changed since the load) the game keeps running.
+    pub fn power_on(&mut self) -> Result<(), LoadError> {
+        let Some(cpu) = self.cpu.as_mut() else { return Ok(()) };
+        let disk = match cpu.bus.mapper.as_fds() {
+            Some(fds) => Some((fds.get_disk_image()?, fds.get_disk_side())),
+            None => None,
         };
+        let image = disk.as_ref().map_or(&self.rom[..], |(image, _)| image);
+        let (mut mapper, ..) = new(image, &self.bios, &String::from_utf8_lossy(&self.database))?;
+        if let (Some(old), Some(ram)) = (cpu.bus.mapper.get_save_ram(), mapper.get_save_ram()) {
+            if old.len() == ram.len() { ram.copy_from_slice(old); }
+        }
+        if let (Some((_, side)), Some(fds)) = (disk, mapper.as_fds()) {
+            match side {
+                Some(side) => fds.insert_disk(side),
+                None => fds.eject_disk(),
+            }
+        }
+        cpu.bus.mapper = mapper;
+        self.boot();
+        Ok(())
+    }
+
+    fn boot(&mut self) {
+        if let Some(cpu) = self.cpu.as_mut() {
+            cpu.bus.power_on(self.ram_init);
+            cpu.power_on();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(cpu) = self.cpu.as_mut() {
+            cpu.bus.reset();
+            cpu.reset();
+        }
     }
 
     pub fn get_frame_pointer(&self) -> *const u32 {
         match self.cpu.as_ref() {
             Some(cpu) => cpu.bus.ppu.frame.get_pointer(),
-            None => { panic!("Emulator not initialized."); }
+            None => std::ptr::null()
+        }
+    }
+
+    // 256x240 pixels, 0xRRGGBBAA.
+    pub fn frame_buffer(&self) -> &[u32] {
+        match self.cpu.as_ref() {
+            Some(cpu) => cpu.bus.ppu.frame.get_buffer(),
+            None => &[]
+        }
+    }
+
+    pub fn run_frame(&mut self) { 
+        if let Some(cpu) = self.cpu.as_mut() {
+            cpu.bus.audio.clear();
+            cpu.run_frame();
+        }
+    }
+
+    pub fn run_cycles(&mut self, cycles: usize) {
+        if let Some(cpu) = self.cpu.as_mut() {
+            cpu.bus.audio.clear();
+            cpu.run_cycles(cycles);
         }
     }
 
-    pub fn step(&mut self) { 
+    // Runs one CPU instruction, returns the cycles it took.
+    pub fn step_instruction(&mut self) -> usize {
         match self.cpu.as_mut() {
-            Some(cpu) => cpu.run(),
-            None => { panic!("Emulator not initialized."); }
+            Some(cpu) => {
+                cpu.bus.audio.clear();
+                cpu.step_instruction()
+            },
+            None => 0
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NROM, reset vector at $8123.
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![b'N', b'E', b'S', 0x1A, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.resize(16 + 0x8000 + 0x2000, 0);
+        rom[16 + 0x7FFC..16 + 0x7FFE].copy_from_slice(&[0x23, 0x81]);
+        rom
+    }
+
+    #[test]
+    fn load_rom_powers_on() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom()).unwrap();
+        let registers = emulator.cpu.as_ref().unwrap().get_registers();
+        assert_eq!((registers.pc, registers.s), (0x8123, 0xFD));
+    }
+
+    #[test]
+    fn nothing_loaded() {
+        let mut emulator = Emulator::new();
+        emulator.run_frame();
+        emulator.power_on().unwrap();
+        emulator.reset();
+        emulator.set_buttons(0, 0xFF);
+        emulator.insert_disk(0);
+        assert_eq!(emulator.step_instruction(), 0);
+        assert_eq!(emulator.get_color(0), None);
+        assert_eq!(emulator.get_halt(), None);
+        assert!(emulator.frame_buffer().is_empty() && emulator.audio().is_empty());
+        assert!(emulator.get_save_ram_pointer().is_null());
+        assert_eq!((emulator.get_disk_sides(), emulator.get_disk_side()), (0, -1));
+
+        // Out of range ports are ignored.
+        emulator.load_rom(&rom()).unwrap();
+        emulator.set_buttons(2, 0xFF);
+        emulator.set_buttons(1, 0x01);
+        assert!(emulator.get_color(32).is_none());
+        assert!(emulator.get_color(0).is_some());
+    }
+
+    // A failed load returns the error and keeps the game already running.
+    fn load_error(rom: &[u8]) -> LoadError {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&self::rom()).unwrap();
+        let error = emulator.load_rom(rom).unwrap_err();
+        assert_eq!(emulator.cpu.as_ref().unwrap().get_registers().pc, 0x8123);
+        error
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut rom = rom();
+        rom[3] = 0x1B;
+        assert_eq!(load_error(&rom), LoadError::UnknownFormat);
+        assert_eq!(load_error(&[]), LoadError::UnknownFormat);
+        assert_eq!(LoadError::UnknownFormat.code(), 1);
+    }
+
+    #[test]
+    fn truncated_prg() {
+        let mut rom = rom();
+        rom.truncate(16 + 0x4000);
+        assert_eq!(load_error(&rom), LoadError::SizeMismatch { expected: 16 + 0x8000 + 0x2000, actual: 16 + 0x4000 });
+        assert_eq!(load_error(&rom[..10]), LoadError::TruncatedHeader);
+    }
+
+    #[test]
+    fn truncated_chr() {
+        let mut rom = rom();
+        rom.pop();
+        assert_eq!(load_error(&rom), LoadError::SizeMismatch { expected: 16 + 0x8000 + 0x2000, actual: 16 + 0x8000 + 0x1FFF });
+        assert_eq!(LoadError::SizeMismatch { expected: 0, actual: 0 }.code(), 3);
+    }
+
+    #[test]
+    fn unsupported_mapper() {
+        let mut rom = rom();
+        rom[6] = 0xF0;
+        rom[7] = 0xF0;
+        assert_eq!(load_error(&rom), LoadError::UnsupportedMapper(255));
+        assert_eq!(LoadError::UnsupportedMapper(255).code(), 5);
+    }
+
+    #[test]
+    fn missing_bios() {
+        let mut disk = b"FDS\x1A\x01".to_vec();
+        disk.resize(16 + 65500, 0);
+        assert_eq!(load_error(&disk), LoadError::MissingBios);
+        let mut emulator = Emulator::new();
+        emulator.set_bios(&[0; 0x1000]);
+        assert_eq!(emulator.load_rom(&disk), Err(LoadError::MissingBios));
+        assert_eq!(LoadError::MissingBios.code(), 7);
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let path = std::env::temp_dir().join(format!("nass-{}-round-trip.sav", std::process::id()));
+        let mut rom = rom();
+        rom[6] = 0x02;
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom).unwrap();
+        assert_eq!(emulator.get_save_ram_length(), 0x2000);
+        for n in 0..0x100 { emulator.cpu.as_mut().unwrap().bus.write(0x6000 + n, n as u8); }
+        emulator.flush_save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap().len(), 0x2000);
+
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom).unwrap();
+        emulator.load_save(&path).unwrap();
+        let cpu = emulator.cpu.as_mut().unwrap();
+        assert!((0..0x100).all(|n| cpu.bus.read(0x6000 + n) == n as u8));
+        fs::remove_file(&path).unwrap();
+        // Nothing saved yet.
+        assert!(emulator.load_save(&path).is_ok());
+    }
+
+    #[test]
+    fn save_wrong_size() {
+        let path = std::env::temp_dir().join(format!("nass-{}-wrong-size.sav", std::process::id()));
+        let mut rom = rom();
+        rom[6] = 0x02;
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom).unwrap();
+        // Short: the rest of save RAM is left alone.
+        emulator.cpu.as_mut().unwrap().bus.write(0x6100, 0x77);
+        fs::write(&path, [0x55; 0x100]).unwrap();
+        emulator.load_save(&path).unwrap();
+        let cpu = emulator.cpu.as_mut().unwrap();
+        assert_eq!((cpu.bus.read(0x60FF), cpu.bus.read(0x6100)), (0x55, 0x77));
+        // Long: the end is dropped.
+        fs::write(&path, [[0x11; 0x2000], [0x22; 0x2000]].concat()).unwrap();
+        emulator.load_save(&path).unwrap();
+        let cpu = emulator.cpu.as_mut().unwrap();
+        assert_eq!(cpu.bus.read(0x7FFF), 0x11);
+        emulator.flush_save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [0x11; 0x2000]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bios() {
+        let mut emulator = Emulator::new();
+        let mut disk = b"FDS\x1A\x01".to_vec();
+        disk.resize(16 + 65500, 0);
+        disk[16] = 0x01;
+        disk[17..32].copy_from_slice(b"*NINTENDO-HVC*\0");
+        assert_eq!(emulator.load_rom(&disk), Err(LoadError::MissingBios));
+        emulator.set_bios(&[0; 0x2000]);
+        assert!(emulator.load_rom(&disk).is_ok());
+        assert_eq!(emulator.get_disk_sides(), 1);
+        emulator.eject_disk();
+        emulator.power_on().unwrap();
+        assert_eq!((emulator.get_disk_sides(), emulator.get_disk_side()), (1, -1));
+    }
+
+    #[test]
+    fn power_on_error() {
+        let mut disk = b"FDS\x1A\x01".to_vec();
+        disk.resize(16 + 65500, 0);
+        disk[16] = 0x01;
+        disk[17..32].copy_from_slice(b"*NINTENDO-HVC*\0");
+        let mut emulator = Emulator::new();
+        emulator.set_bios(&[0; 0x2000]);
+        emulator.load_rom(&disk).unwrap();
+        // BRK (the BIOS is blank) pushes 3 bytes.
+        emulator.step_instruction();
+        assert_eq!(emulator.cpu.as_ref().unwrap().get_registers().s, 0xFA);
+        // The BIOS was replaced since the load, the game keeps running.
+        emulator.set_bios(&[0; 0x1000]);
+        assert_eq!(emulator.power_on(), Err(LoadError::MissingBios));
+        assert_eq!(emulator.cpu.as_ref().unwrap().get_registers().s, 0xFA);
+        emulator.set_bios(&[0; 0x2000]);
+        assert!(emulator.power_on().is_ok());
+        assert_eq!(emulator.cpu.as_ref().unwrap().get_registers().s, 0xFD);
+    }
+
+    #[test]
+    fn power_on_rebuilds_the_mapper() {
+        // CNROM, the second CHR bank is filled with 1.
+        let mut rom = rom();
+        rom[5] = 2;
+        rom[6] = 0x30;
+        rom.resize(16 + 0x8000 + 0x4000, 1);
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom).unwrap();
+        let cpu = emulator.cpu.as_mut().unwrap();
+        cpu.bus.write(0x8000, 1);
+        assert_eq!(cpu.bus.mapper.read_chr(0), 1);
+        emulator.power_on().unwrap();
+        assert_eq!(emulator.cpu.as_mut().unwrap().bus.mapper.read_chr(0), 0);
+    }
+
+    #[test]
+    fn power_on_keeps_battery_ram() {
+        for (flags_6, kept) in [(0x00, 0), (0x02, 0x55)] {
+            let mut rom = rom();
+            rom[6] = flags_6;
+            let mut emulator = Emulator::new();
+            emulator.load_rom(&rom).unwrap();
+            emulator.cpu.as_mut().unwrap().bus.write(0x6000, 0x55);
+            emulator.power_on().unwrap();
+            assert_eq!(emulator.cpu.as_mut().unwrap().bus.read(0x6000), kept);
+        }
+    }
+
+    #[test]
+    fn power_on_clears_the_joypads() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom()).unwrap();
+        emulator.set_buttons(0, 0x02);
+        let cpu = emulator.cpu.as_mut().unwrap();
+        cpu.bus.write(0x4016, 1);
+        cpu.bus.write(0x4016, 0);
+        let bits: Vec<u8> = (0..9).map(|_| cpu.bus.read(0x4016) & 1).collect();
+        assert_eq!(bits, [0, 1, 0, 0, 0, 0, 0, 0, 1]);
+        // The held buttons stay, the shift register starts over.
+        emulator.power_on().unwrap();
+        let cpu = emulator.cpu.as_mut().unwrap();
+        assert_eq!((cpu.bus.read(0x4016) & 1, cpu.bus.read(0x4016) & 1), (0, 1));
+    }
+}
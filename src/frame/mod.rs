@@ -1,15 +1,21 @@
 pub struct Frame {
-    frame: [u32; Frame::WIDTH*Frame::HEIGHT],
+    frame: Vec<u32>, // On the heap, the PPU is moved around by value
     index: usize,
 }
 
+impl Default for Frame {
+    fn default() -> Self {
+        Frame::new()
+    }
+}
+
 impl Frame {
     pub const WIDTH: usize = 256;
     pub const HEIGHT: usize = 240;
 
     pub fn new() -> Frame {
         Frame { 
-            frame: [0xFF; Frame::WIDTH*Frame::HEIGHT],
+            frame: vec![0xFF; Frame::WIDTH*Frame::HEIGHT],
             index: 0,
         }
     }
@@ -25,4 +31,8 @@ impl Frame {
     pub fn get_pointer(&self) -> *const u32 {
         self.frame.as_ptr()
     }
+
+    pub fn get_buffer(&self) -> &[u32] {
+        &self.frame
+    }
 }
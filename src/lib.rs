@@ -8,55 +8,259 @@ mod frame;
 
 use { 
     cfg_if::cfg_if,
-    std::cell::RefCell,
-    crate::emulator::Emulator,
+    std::{ cell::RefCell, ptr },
 };
 
+pub use crate::{ emulator::Emulator, mapper::LoadError, cpu::{ JoypadButton, Halt, RamInit, CPU, Bus, Registers } };
+
 cfg_if! {
-    if #[cfg(feature = "wee_alloc")] {
+    if #[cfg(all(feature = "wee_alloc", target_arch = "wasm32"))] {
         #[global_allocator]
         static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
     }
 }
 
-thread_local!{ static EMULATOR: RefCell<Emulator> = RefCell::new(Emulator::new()) }
+// Emulators created from JS, indexed by handle (None once destroyed).
+thread_local!{ static EMULATORS: RefCell<Vec<Option<Emulator>>> = const { RefCell::new(Vec::new()) } }
+
+// Calls on a handle that was never created or is destroyed return `invalid` (JS can't
+// recover from a trap).
+fn with_emulator<R>(handle: usize, invalid: R, f: impl FnOnce(&mut Emulator) -> R) -> R {
+    EMULATORS.with_borrow_mut(|emulators| match emulators.get_mut(handle).and_then(Option::as_mut) {
+        Some(e) => f(e),
+        None => invalid,
+    })
+}
+
+#[no_mangle]
+pub fn create_emulator() -> usize {
+    EMULATORS.with_borrow_mut(|emulators| match emulators.iter().position(Option::is_none) {
+        Some(handle) => {
+            emulators[handle] = Some(Emulator::new());
+            handle
+        },
+        None => {
+            emulators.push(Some(Emulator::new()));
+            emulators.len() - 1
+        }
+    })
+}
+
+#[no_mangle]
+pub fn destroy_emulator(handle: usize) {
+    EMULATORS.with_borrow_mut(|emulators| if let Some(emulator) = emulators.get_mut(handle) { *emulator = None; })
+}
+
+#[no_mangle]
+pub fn set_rom_length(handle: usize, value: usize) {
+    with_emulator(handle, (), |e| e.set_len(value))
+}
+
+// 0 on success, otherwise a `LoadError` code (-1: invalid handle).
+#[no_mangle]
+pub fn disassemble(handle: usize) -> i32 {
+    with_emulator(handle, -1, |e| e.disassemble().map_or_else(|error| error.code(), |_| 0))
+}
+
+// 0: zeros, 1: $FF, 2: power-on pattern, 3: random from `seed`. Applies to the next power on.
+#[no_mangle]
+pub fn set_ram_init(handle: usize, policy: u8, seed: u32) {
+    let ram_init = match policy {
+        1 => RamInit::Ones,
+        2 => RamInit::Pattern,
+        3 => RamInit::Random(seed as u64),
+        _ => RamInit::Zeros,
+    };
+    with_emulator(handle, (), |e| e.set_ram_init(ram_init))
+}
+
+// 0 on success, otherwise a `LoadError` code (-1: invalid handle).
+#[no_mangle]
+pub fn power_on(handle: usize) -> i32 {
+    with_emulator(handle, -1, |e| e.power_on().map_or_else(|error| error.code(), |_| 0))
+}
+
+#[no_mangle]
+pub fn reset(handle: usize) {
+    with_emulator(handle, (), |e| e.reset());
+}
+
+#[no_mangle]
+pub fn run_frame(handle: usize) {
+    with_emulator(handle, (), |e| e.run_frame());
+}
+
+// -1 while running, otherwise PC << 8 | opcode of the JAM that halted the CPU.
+#[no_mangle]
+pub fn get_halt(handle: usize) -> i32 {
+    with_emulator(handle, -1, |e| e.get_halt().map_or(-1, |halt| (halt.pc as i32) << 8 | halt.opcode as i32))
+}
+
+#[no_mangle]
+pub fn run_cycles(handle: usize, cycles: usize) {
+    with_emulator(handle, (), |e| e.run_cycles(cycles));
+}
+
+#[no_mangle]
+pub fn step_instruction(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.step_instruction())
+}
+
+#[no_mangle]
+pub fn toggle_button(handle: usize, value: u8) {
+    with_emulator(handle, (), |e| e.toggle_button(value))
+}
+
+#[no_mangle]
+pub fn set_buttons(handle: usize, port: usize, mask: u8) {
+    with_emulator(handle, (), |e| e.set_buttons(port, mask))
+}
+
+#[no_mangle]
+pub fn get_frame_pointer(handle: usize) -> *const u32 {
+    with_emulator(handle, ptr::null(), |e| e.get_frame_pointer())
+}
+
+#[no_mangle]
+pub fn get_rom_pointer(handle: usize) -> *mut u8 {
+    with_emulator(handle, ptr::null_mut(), |e| e.get_rom_pointer())
+}
+
+#[no_mangle]
+pub fn get_color(handle: usize, index: usize) -> u32 {
+    with_emulator(handle, 0, |e| e.get_color(index).unwrap_or(0))
+}
+
+#[no_mangle]
+pub fn get_corrections(handle: usize) -> u16 {
+    with_emulator(handle, 0, |e| e.get_corrections())
+}
+
+#[no_mangle]
+pub fn is_vs_system(handle: usize) -> bool {
+    with_emulator(handle, false, |e| e.is_vs_system())
+}
+
+#[no_mangle]
+pub fn set_dip_switches(handle: usize, value: u8) {
+    with_emulator(handle, (), |e| e.set_dip_switches(value))
+}
+
+#[no_mangle]
+pub fn toggle_coin(handle: usize, value: u8) {
+    with_emulator(handle, (), |e| e.toggle_coin(value))
+}
+
+#[no_mangle]
+pub fn has_battery(handle: usize) -> bool {
+    with_emulator(handle, false, |e| e.has_battery())
+}
+
+#[no_mangle]
+pub fn get_save_ram_pointer(handle: usize) -> *mut u8 {
+    with_emulator(handle, ptr::null_mut(), |e| e.get_save_ram_pointer())
+}
 
 #[no_mangle]
-pub fn set_rom_length(value: usize) {
-    EMULATOR.with_borrow_mut(|e| e.set_len(value))
+pub fn get_save_ram_length(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.get_save_ram_length())
 }
 
 #[no_mangle]
-pub fn disassemble() {
-    EMULATOR.with_borrow_mut(|e| e.disassemble());
+pub fn set_bios_length(handle: usize, value: usize) {
+    with_emulator(handle, (), |e| e.set_bios_len(value))
 }
 
 #[no_mangle]
-pub fn reset() {
-    EMULATOR.with_borrow_mut(|e| e.reset());
+pub fn get_bios_pointer(handle: usize) -> *mut u8 {
+    with_emulator(handle, ptr::null_mut(), |e| e.get_bios_pointer())
 }
 
+// Extra game database entries (database.txt format), written like the BIOS.
 #[no_mangle]
-pub fn step() {
-    EMULATOR.with_borrow_mut(|e| e.step());
+pub fn set_database_length(handle: usize, value: usize) {
+    with_emulator(handle, (), |e| e.set_database_len(value))
 }
 
 #[no_mangle]
-pub fn toggle_button(value: u8) {
-    EMULATOR.with_borrow_mut(|e| e.toggle_button(value))
+pub fn get_database_pointer(handle: usize) -> *mut u8 {
+    with_emulator(handle, ptr::null_mut(), |e| e.get_database_pointer())
 }
 
 #[no_mangle]
-pub fn get_frame_pointer() -> *const u32 {
-    EMULATOR.with_borrow_mut(|e| e.get_frame_pointer())
+pub fn get_disk_sides(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.get_disk_sides())
 }
 
 #[no_mangle]
-pub fn get_rom_pointer() -> *const u8 {
-    EMULATOR.with_borrow_mut(|e| e.get_rom_pointer())
+pub fn get_disk_side(handle: usize) -> isize {
+    with_emulator(handle, -1, |e| e.get_disk_side())
 }
 
 #[no_mangle]
-pub fn get_color(index: usize) -> u32 {
-    EMULATOR.with_borrow_mut(|e| e.get_color(index))
+pub fn insert_disk(handle: usize, side: usize) {
+    with_emulator(handle, (), |e| e.insert_disk(side))
+}
+
+#[no_mangle]
+pub fn eject_disk(handle: usize) {
+    with_emulator(handle, (), |e| e.eject_disk())
+}
+
+#[no_mangle]
+pub fn save_disk(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.save_disk())
+}
+
+#[no_mangle]
+pub fn get_disk_image_pointer(handle: usize) -> *const u8 {
+    with_emulator(handle, ptr::null(), |e| e.get_disk_image_pointer())
+}
+
+#[no_mangle]
+pub fn get_debug_state(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.get_debug_state())
+}
+
+#[no_mangle]
+pub fn get_debug_state_pointer(handle: usize) -> *const u8 {
+    with_emulator(handle, ptr::null(), |e| e.get_debug_state_pointer())
+}
+
+#[no_mangle]
+pub fn get_audio_pointer(handle: usize) -> *const f32 {
+    with_emulator(handle, ptr::null(), |e| e.get_audio_pointer())
+}
+
+#[no_mangle]
+pub fn get_audio_length(handle: usize) -> usize {
+    with_emulator(handle, 0, |e| e.get_audio_length())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destroyed_handle() {
+        let handle = create_emulator();
+        destroy_emulator(handle);
+        // Nothing traps, everything reports the invalid value.
+        run_frame(handle);
+        set_buttons(handle, 0, 0xFF);
+        assert_eq!(disassemble(handle), -1);
+        assert_eq!(get_halt(handle), -1);
+        assert_eq!(get_disk_side(handle), -1);
+        assert_eq!(get_audio_length(handle), 0);
+        assert!(!has_battery(handle));
+        assert!(get_frame_pointer(handle).is_null());
+        assert!(get_rom_pointer(handle).is_null());
+        // Never created.
+        run_frame(usize::MAX);
+        assert_eq!(disassemble(usize::MAX), -1);
+        // The slot is reused.
+        assert_eq!(create_emulator(), handle);
+        assert!(!get_rom_pointer(handle).is_null());
+        destroy_emulator(handle);
+    }
 }
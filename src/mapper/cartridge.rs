@@ -0,0 +1,226 @@
+use super::{ Header, LoadError };
+
+pub const PRG_PAGE_SIZE: usize = 0x2000; // 8KB pages over $6000-$FFFF
+pub const CHR_PAGE_SIZE: usize = 0x400; // 1KB pages over $0000-$1FFF
+const PRG_PAGES: usize = 5;
+const CHR_PAGES: usize = 8;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Memory {
+    Rom,
+    Ram,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Page {
+    pub memory: Memory,
+    pub offset: usize,
+}
+
+// Owns the cartridge memory. Mappers only move the page tables around,
+// so every read is a table lookup into a buffer of known size.
+pub struct Cartridge {
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+    chr_ram: Vec<u8>,
+    battery: bool,
+    prg_pages: [Option<Page>; PRG_PAGES],
+    chr_pages: [Option<Page>; CHR_PAGES],
+    pub open_bus: u8, // CPU data bus, what unmapped PRG reads return
+}
+
+impl Cartridge {
+    pub fn new(prg_rom: Vec<u8>, chr_rom: Vec<u8>, prg_ram_size: usize, chr_ram_size: usize, battery: bool) -> Self {
+        // Whole pages only, so a mapped page never runs past the end of its buffer.
+        Cartridge {
+            prg_rom: Cartridge::pad(prg_rom, PRG_PAGE_SIZE),
+            chr_rom: Cartridge::pad(chr_rom, CHR_PAGE_SIZE),
+            prg_ram: Cartridge::pad(vec![0; prg_ram_size], PRG_PAGE_SIZE),
+            chr_ram: Cartridge::pad(vec![0; chr_ram_size], CHR_PAGE_SIZE),
+            battery,
+            prg_pages: [None; PRG_PAGES],
+            chr_pages: [None; CHR_PAGES],
+            open_bus: 0,
+        }
+    }
+
+    fn pad(mut memory: Vec<u8>, page_size: usize) -> Vec<u8> {
+        memory.resize(memory.len().div_ceil(page_size) * page_size, 0);
+        memory
+    }
+
+    pub fn from_header(header: &Header, bytes: &[u8]) -> Result<Self, LoadError> {
+        let prg_end = header.prg_offset.saturating_add(header.prg_rom_size);
+        let chr_end = header.chr_offset.saturating_add(header.chr_rom_size);
+        let (Some(prg_rom), Some(chr_rom)) = (bytes.get(header.prg_offset..prg_end), bytes.get(header.chr_offset..chr_end)) else {
+            return Err(LoadError::SizeMismatch { expected: prg_end.max(chr_end), actual: bytes.len() })
+        };
+        let mut chr_ram_size = header.chr_ram_size + header.chr_nvram_size;
+        if chr_rom.is_empty() && chr_ram_size == 0 { chr_ram_size = 0x2000; }
+        Ok(Cartridge::new(
+            prg_rom.to_vec(),
+            chr_rom.to_vec(),
+            header.prg_ram_size + header.prg_nvram_size,
+            chr_ram_size,
+            header.battery,
+        ))
+    }
+
+    fn len(&self, memory: Memory, chr: bool) -> usize {
+        match (memory, chr) {
+            (Memory::Rom, false) => self.prg_rom.len(),
+            (Memory::Ram, false) => self.prg_ram.len(),
+            (Memory::Rom, true) => self.chr_rom.len(),
+            (Memory::Ram, true) => self.chr_ram.len(),
+        }
+    }
+
+    // Number of `size` banks in a memory, at least 1.
+    pub fn prg_banks(&self, memory: Memory, size: usize) -> usize {
+        (self.len(memory, false) / size).max(1)
+    }
+
+    // Boards have either CHR ROM or CHR RAM.
+    pub fn chr_memory(&self) -> Memory {
+        if self.chr_rom.is_empty() { Memory::Ram } else { Memory::Rom }
+    }
+
+    // Maps `size` bytes at `addr` ($6000-$FFFF) to `bank` (in `size` units), banks wrap around the memory size.
+    pub fn map_prg(&mut self, addr: u16, size: usize, memory: Memory, bank: usize) {
+        let len = self.len(memory, false);
+        let first = (addr as usize - 0x6000) / PRG_PAGE_SIZE;
+        for (n, page) in self.prg_pages[first..first + size / PRG_PAGE_SIZE].iter_mut().enumerate() {
+            *page = if len == 0 { None } else {
+                Some(Page { memory, offset: (bank * size + n * PRG_PAGE_SIZE) % len })
+            };
+        }
+    }
+
+    pub fn unmap_prg(&mut self, addr: u16, size: usize) {
+        let first = (addr as usize - 0x6000) / PRG_PAGE_SIZE;
+        self.prg_pages[first..first + size / PRG_PAGE_SIZE].fill(None);
+    }
+
+    pub fn map_chr(&mut self, addr: u16, size: usize, memory: Memory, bank: usize) {
+        let len = self.len(memory, true);
+        let first = addr as usize / CHR_PAGE_SIZE;
+        for (n, page) in self.chr_pages[first..first + size / CHR_PAGE_SIZE].iter_mut().enumerate() {
+            *page = if len == 0 { None } else {
+                Some(Page { memory, offset: (bank * size + n * CHR_PAGE_SIZE) % len })
+            };
+        }
+    }
+
+    pub fn get_prg_page(&self, addr: u16) -> Option<Page> {
+        self.prg_pages[(addr as usize - 0x6000) / PRG_PAGE_SIZE]
+    }
+
+    pub fn get_chr_page(&self, addr: u16) -> Option<Page> {
+        self.chr_pages[(addr as usize & 0x1FFF) / CHR_PAGE_SIZE]
+    }
+
+    pub fn read_prg(&self, addr: u16) -> u8 {
+        if addr < 0x6000 { return self.open_bus }
+        match self.get_prg_page(addr) {
+            Some(Page { memory: Memory::Rom, offset }) => self.prg_rom[offset + (addr as usize & (PRG_PAGE_SIZE - 1))],
+            Some(Page { memory: Memory::Ram, offset }) => self.prg_ram[offset + (addr as usize & (PRG_PAGE_SIZE - 1))],
+            None => self.open_bus
+        }
+    }
+
+    pub fn write_prg(&mut self, addr: u16, val: u8) {
+        if addr < 0x6000 { return }
+        if let Some(Page { memory: Memory::Ram, offset }) = self.get_prg_page(addr) {
+            self.prg_ram[offset + (addr as usize & (PRG_PAGE_SIZE - 1))] = val;
+        }
+    }
+
+    pub fn read_chr(&self, addr: u16) -> u8 {
+        match self.get_chr_page(addr) {
+            Some(Page { memory: Memory::Rom, offset }) => self.chr_rom[offset + (addr as usize & (CHR_PAGE_SIZE - 1))],
+            Some(Page { memory: Memory::Ram, offset }) => self.chr_ram[offset + (addr as usize & (CHR_PAGE_SIZE - 1))],
+            None => 0
+        }
+    }
+
+    pub fn write_chr(&mut self, addr: u16, val: u8) {
+        if let Some(Page { memory: Memory::Ram, offset }) = self.get_chr_page(addr) {
+            self.chr_ram[offset + (addr as usize & (CHR_PAGE_SIZE - 1))] = val;
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.battery && !self.prg_ram.is_empty()
+    }
+
+    pub fn get_save_ram(&mut self) -> Option<&mut [u8]> {
+        if self.has_battery() { Some(&mut self.prg_ram) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every byte holds the number of its `size` bank.
+    fn banks(len: usize, size: usize) -> Vec<u8> {
+        (0..len).map(|i| (i / size) as u8).collect()
+    }
+
+    #[test]
+    fn prg_banks_wrap() {
+        let mut cartridge = Cartridge::new(banks(0x8000, PRG_PAGE_SIZE), Vec::new(), 0, 0x2000, false);
+        assert_eq!(cartridge.prg_banks(Memory::Rom, 0x4000), 2);
+        cartridge.map_prg(0x8000, 0x4000, Memory::Rom, 1);
+        assert_eq!((cartridge.read_prg(0x8000), cartridge.read_prg(0xBFFF)), (2, 3));
+        cartridge.map_prg(0xC000, 0x4000, Memory::Rom, 3);
+        assert_eq!(cartridge.read_prg(0xC000), 2);
+        // No PRG RAM, nothing mapped at $6000.
+        cartridge.map_prg(0x6000, 0x2000, Memory::Ram, 0);
+        cartridge.write_prg(0x6000, 0x55);
+        assert_eq!(cartridge.read_prg(0x6000), 0);
+        cartridge.unmap_prg(0x8000, 0x4000);
+        assert_eq!(cartridge.get_prg_page(0x8000).map(|page| page.offset), None);
+    }
+
+    #[test]
+    fn prg_ram_and_battery() {
+        let mut cartridge = Cartridge::new(banks(0x4000, PRG_PAGE_SIZE), Vec::new(), 0x4000, 0x2000, true);
+        cartridge.map_prg(0x6000, 0x2000, Memory::Ram, 1);
+        cartridge.map_prg(0x8000, 0x4000, Memory::Rom, 0);
+        cartridge.write_prg(0x6001, 0x55);
+        cartridge.write_prg(0x8000, 0x55);
+        assert_eq!((cartridge.read_prg(0x6001), cartridge.read_prg(0x8000)), (0x55, 0));
+        assert_eq!(cartridge.get_save_ram().map(|ram| ram[0x2001]), Some(0x55));
+        assert!(Cartridge::new(Vec::new(), Vec::new(), 0x2000, 0, false).get_save_ram().is_none());
+        assert!(!Cartridge::new(Vec::new(), Vec::new(), 0, 0, true).has_battery());
+    }
+
+    #[test]
+    fn chr_rom_and_ram() {
+        let mut cartridge = Cartridge::new(Vec::new(), banks(0x2000, CHR_PAGE_SIZE), 0, 0, false);
+        assert_eq!(cartridge.chr_memory(), Memory::Rom);
+        cartridge.map_chr(0x0000, 0x2000, Memory::Rom, 0);
+        cartridge.map_chr(0x0400, 0x0400, Memory::Rom, 13);
+        assert_eq!((cartridge.read_chr(0x0000), cartridge.read_chr(0x07FF), cartridge.read_chr(0x1C00)), (0, 5, 7));
+        cartridge.write_chr(0x0000, 0x55);
+        assert_eq!(cartridge.read_chr(0x0000), 0);
+
+        let mut cartridge = Cartridge::new(Vec::new(), Vec::new(), 0, 0x2000, false);
+        assert_eq!(cartridge.chr_memory(), Memory::Ram);
+        cartridge.map_chr(0x0000, 0x1000, Memory::Ram, 1);
+        cartridge.write_chr(0x0000, 0x55);
+        assert_eq!(cartridge.read_chr(0x0000), 0x55);
+        cartridge.map_chr(0x1000, 0x1000, Memory::Ram, 1);
+        assert_eq!(cartridge.read_chr(0x1000), 0x55);
+    }
+
+    // Memory is padded to whole pages, a short ROM reads 0 past its end.
+    #[test]
+    fn padding() {
+        let mut cartridge = Cartridge::new(vec![1; 0x1000], Vec::new(), 0, 0, false);
+        cartridge.map_prg(0x8000, 0x2000, Memory::Rom, 0);
+        assert_eq!((cartridge.read_prg(0x8FFF), cartridge.read_prg(0x9000)), (1, 0));
+    }
+}
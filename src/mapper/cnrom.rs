@@ -1,22 +1,23 @@
 use std::fmt;
 use super::*;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CNROM {
-    chr_bank: u16,
+    chr_bank: u8,
+    cartridge: Cartridge,
     mirroring: Mirroring,
-    prg_offset: usize,
-    prg_len: usize,
-    chr_offset: usize,
 }
 
 impl CNROM {
-    pub fn new(prg_len: usize, _: usize, prg_offset: usize, chr_offset: usize,  mirroring: Mirroring) -> Self { 
+    pub fn new(header: &Header, mut cartridge: Cartridge) -> Self { 
+        cartridge.map_prg(0x8000, 0x4000, Memory::Rom, 0);
+        cartridge.map_prg(0xC000, 0x4000, Memory::Rom, 1);
+        let chr = cartridge.chr_memory();
+        cartridge.map_chr(0x0000, 0x2000, chr, 0);
         CNROM {
-            prg_offset,
-            prg_len,
-            chr_offset,
             chr_bank: 0,
-            mirroring,
+            cartridge,
+            mirroring: header.mirroring,
         } 
     }
 }
@@ -28,34 +29,20 @@ impl fmt::Display for CNROM {
 }
 
 impl Mapper for CNROM {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
     fn get_mirroring(&self) -> Mirroring { self.mirroring }
 
-    fn read_chr(&self, rom: *const u8, addr: u16) -> u8 { 
-        let addr = (addr + self.chr_bank) as usize;
-        unsafe { *(rom.wrapping_add(self.chr_offset + (addr as usize))) } 
-    }
-
-    fn read_prg(&self, rom: *const u8, addr: u16) -> u8 { 
-        match addr {
-            0x8000..=0xFFFF => {
-                let mut addr = addr - 0x8000;
-                if self.prg_len == 0x4000 && addr >= 0x4000 { 
-                    addr = addr % 0x4000; 
-                }
-                unsafe { *(rom.wrapping_add(self.prg_offset + (addr as usize))) } 
-            },
-            _ => 0
-        }
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        vec![("chr".to_string(), self.chr_bank as u32)]
     }
 
     fn write_prg(&mut self, addr: u16, val: u8) { 
-        match addr {
-            0x8000..=0xFFFF => {
-                self.chr_bank = ((val as u16) & 0x3) * 0x2000;
-            },
-            _ => () 
+        if let 0x8000..=0xFFFF = addr {
+            self.chr_bank = val;
+            let chr = self.cartridge.chr_memory();
+            self.cartridge.map_chr(0x0000, 0x2000, chr, val as usize);
         }
     }
-
-    fn write_chr(&mut self, _: u16, _: u8) {}
 }
@@ -0,0 +1,256 @@
+use bitflags::bitflags;
+use super::{ Header, Mirroring, Timing, Console };
+
+// One game per line, keyed by the CRC32 of PRG+CHR (no header, no trainer):
+// crc32,mapper,submapper,mirroring,prg_ram,prg_nvram,chr_ram,timing,input,console # name
+// mirroring: H, V, 4 or - (mapper controlled), timing: ntsc, pal, multi or dendy,
+// input: NES 2.0 default expansion device number,
+// console: nes, pc10 or vs-<ppu>-<hardware> (NES 2.0 Vs. PPU and hardware types).
+const DATABASE: &str = include_str!("database.txt");
+
+bitflags! {
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    pub struct Corrections: u16 {
+        const MAPPER    = 0b000000001;
+        const SUBMAPPER = 0b000000010;
+        const MIRRORING = 0b000000100;
+        const PRG_RAM   = 0b000001000;
+        const CHR_RAM   = 0b000010000;
+        const BATTERY   = 0b000100000;
+        const TIMING    = 0b001000000;
+        const INPUT     = 0b010000000;
+        const CONSOLE   = 0b100000000;
+    }
+}
+
+struct Entry {
+    mapper: u16,
+    submapper: u8,
+    mirroring: Option<Mirroring>,
+    prg_ram_size: usize,
+    prg_nvram_size: usize,
+    chr_ram_size: usize,
+    timing: Timing,
+    input: u8,
+    console: Console,
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn parse_entry(line: &str) -> Option<(u32, Entry)> {
+    let line = line.split('#').next()?.trim();
+    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+    if fields.len() != 10 { return None }
+    let crc = u32::from_str_radix(fields[0], 16).ok()?;
+    let entry = Entry {
+        mapper: fields[1].parse().ok()?,
+        submapper: fields[2].parse().ok()?,
+        mirroring: match fields[3] {
+            "H" => Some(Mirroring::Horizontal),
+            "V" => Some(Mirroring::Vertical),
+            "4" => Some(Mirroring::FourScreen),
+            _ => None,
+        },
+        prg_ram_size: fields[4].parse().ok()?,
+        prg_nvram_size: fields[5].parse().ok()?,
+        chr_ram_size: fields[6].parse().ok()?,
+        timing: match fields[7] {
+            "pal" => Timing::Pal,
+            "multi" => Timing::MultiRegion,
+            "dendy" => Timing::Dendy,
+            _ => Timing::Ntsc,
+        },
+        input: fields[8].parse().ok()?,
+        console: match fields[9].split('-').collect::<Vec<_>>()[..] {
+            ["vs", ppu, hardware] => Console::VsSystem { ppu: ppu.parse().ok()?, hardware: hardware.parse().ok()? },
+            ["pc10"] => Console::Playchoice,
+            _ => Console::Nes,
+        },
+    };
+    Some((crc, entry))
+}
+
+// Entries in `extra` (same format) come before the built-in ones.
+fn find(crc: u32, extra: &str) -> Option<Entry> {
+    extra.lines().chain(DATABASE.lines())
+        .filter_map(parse_entry)
+        .find(|(entry_crc, _)| *entry_crc == crc)
+        .map(|(_, entry)| entry)
+}
+
+// Overrides the header with the database entry for this PRG+CHR data, if any.
+pub fn correct(header: &mut Header, bytes: &[u8], extra: &str) -> Corrections {
+    let mut corrections = Corrections::empty();
+    let end = (header.chr_offset + header.chr_rom_size).min(bytes.len());
+    let Some(data) = bytes.get(header.prg_offset..end) else { return corrections };
+    let Some(entry) = find(crc32(data), extra) else { return corrections };
+
+    if header.mapper != entry.mapper {
+        header.mapper = entry.mapper;
+        corrections |= Corrections::MAPPER;
+    }
+    if header.submapper != entry.submapper {
+        header.submapper = entry.submapper;
+        corrections |= Corrections::SUBMAPPER;
+    }
+    if let Some(mirroring) = entry.mirroring {
+        if header.mirroring != mirroring {
+            header.mirroring = mirroring;
+            corrections |= Corrections::MIRRORING;
+        }
+    }
+    if header.battery != (entry.prg_nvram_size != 0) {
+        header.battery = entry.prg_nvram_size != 0;
+        corrections |= Corrections::BATTERY;
+    }
+    if header.prg_ram_size != entry.prg_ram_size || header.prg_nvram_size != entry.prg_nvram_size {
+        header.prg_ram_size = entry.prg_ram_size;
+        header.prg_nvram_size = entry.prg_nvram_size;
+        corrections |= Corrections::PRG_RAM;
+    }
+    if header.chr_ram_size != entry.chr_ram_size {
+        header.chr_ram_size = entry.chr_ram_size;
+        corrections |= Corrections::CHR_RAM;
+    }
+    if header.timing != entry.timing {
+        header.timing = entry.timing;
+        corrections |= Corrections::TIMING;
+    }
+    if header.expansion != entry.input {
+        header.expansion = entry.input;
+        corrections |= Corrections::INPUT;
+    }
+    if header.console != entry.console {
+        header.console = entry.console;
+        corrections |= Corrections::CONSOLE;
+    }
+    corrections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // iNES 1.0 NROM-128, horizontal, no battery, with a PRG byte that makes the CRC unique.
+    fn rom(seed: u8) -> Vec<u8> {
+        let mut bytes = b"NES\x1A\x01\x01\x00\x00".to_vec();
+        bytes.resize(16, 0);
+        bytes.extend((0..0x6000).map(|i| (i as u8).wrapping_mul(seed)));
+        bytes
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn entries() {
+        let (crc, entry) = parse_entry("0123ABCD, 4, 1, V, 0, 8192, 0, pal, 1, nes # Game (E)").unwrap();
+        assert_eq!(crc, 0x0123ABCD);
+        assert_eq!((entry.mapper, entry.submapper), (4, 1));
+        assert_eq!(entry.mirroring, Some(Mirroring::Vertical));
+        assert_eq!((entry.prg_ram_size, entry.prg_nvram_size, entry.chr_ram_size), (0, 8192, 0));
+        assert_eq!((entry.timing, entry.input, entry.console), (Timing::Pal, 1, Console::Nes));
+
+        // (line, mirroring, timing, console)
+        let cases = [
+            ("1,99,0,-,0,0,0,ntsc,0,vs-4-0", None, Timing::Ntsc, Console::VsSystem { ppu: 4, hardware: 0 }),
+            ("1,0,0,H,0,0,0,multi,0,pc10", Some(Mirroring::Horizontal), Timing::MultiRegion, Console::Playchoice),
+            ("1,0,0,4,0,0,8192,dendy,0,nes", Some(Mirroring::FourScreen), Timing::Dendy, Console::Nes),
+        ];
+        for (line, mirroring, timing, console) in cases {
+            let (_, entry) = parse_entry(line).unwrap();
+            assert_eq!((entry.mirroring, entry.timing, entry.console), (mirroring, timing, console), "{line}");
+        }
+
+        for line in ["", "# comment", "1,0,0,H,0,0,0,ntsc,0", "1,0,0,H,0,0,0,ntsc,0,nes,extra", "XYZ,0,0,H,0,0,0,ntsc,0,nes",
+            "1,mmc1,0,H,0,0,0,ntsc,0,nes", "1,0,0,H,0,0,0,ntsc,0,vs-a-0", "1,0,300,H,0,0,0,ntsc,0,nes"] {
+            assert!(parse_entry(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn corrections() {
+        let bytes = rom(3);
+        let crc = crc32(&bytes[16..]);
+        let database = format!("# test\n{crc:08X},99,0,V,0,8192,0,ntsc,0,vs-4-0 # VS. game\n");
+        let mut header = Header::new(&bytes).unwrap();
+        let corrections = correct(&mut header, &bytes, &database);
+        assert_eq!(corrections, Corrections::MAPPER | Corrections::MIRRORING | Corrections::BATTERY | Corrections::PRG_RAM | Corrections::CONSOLE);
+        assert_eq!(header.mapper, 99);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.battery);
+        assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 8192));
+        assert_eq!(header.console, Console::VsSystem { ppu: 4, hardware: 0 });
+
+        // Same values as the header: found, nothing to correct.
+        let database = format!("{crc:08X},0,0,H,8192,0,0,ntsc,0,nes\n");
+        let mut header = Header::new(&bytes).unwrap();
+        assert_eq!(correct(&mut header, &bytes, &database), Corrections::empty());
+
+        // Other games are left alone.
+        let other = rom(5);
+        let mut header = Header::new(&other).unwrap();
+        assert_eq!(correct(&mut header, &other, &format!("{crc:08X},99,0,V,0,8192,0,pal,2,nes")), Corrections::empty());
+        assert_eq!((header.mapper, header.mirroring), (0, Mirroring::Horizontal));
+    }
+
+    fn table(index: u32) -> u32 {
+        (0..8).fold(index, |crc, _| if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 })
+    }
+
+    // Four bytes that give `data` followed by them the CRC `target`: the table entries are
+    // found from the end (their top bytes are unique), then the bytes that select them.
+    fn forge(data: &[u8], target: u32) -> [u8; 4] {
+        let mut indices = [0; 4];
+        let mut crc = !target;
+        for index in indices.iter_mut().rev() {
+            *index = (0..256).find(|&i| table(i) >> 24 == crc >> 24).unwrap();
+            crc = (crc ^ table(*index)) << 8;
+        }
+        let mut crc = !crc32(data);
+        indices.map(|index| {
+            let byte = (crc ^ index) as u8;
+            crc = (crc >> 8) ^ table(index);
+            byte
+        })
+    }
+
+    #[test]
+    fn built_in_entries() {
+        let lines = DATABASE.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+        assert!(lines.clone().count() > 0);
+        assert!(lines.clone().all(|line| parse_entry(line).is_some()));
+
+        // NROM-256 with the CRC of Super Mario Bros. and a horizontal mirroring header.
+        let mut bytes = b"NES\x1A\x02\x01\x00\x00".to_vec();
+        bytes.resize(16 + 0xA000 - 4, 0);
+        let tail = forge(&bytes[16..], 0x3337EC46);
+        bytes.extend(tail);
+        assert_eq!(crc32(&bytes[16..]), 0x3337EC46);
+        let mut emulator = crate::Emulator::new();
+        emulator.load_rom(&bytes).unwrap();
+        assert_eq!(emulator.get_corrections(), (Corrections::MIRRORING | Corrections::PRG_RAM).bits());
+    }
+
+    #[test]
+    fn nes2_headers_are_trusted() {
+        let mut bytes = rom(7);
+        let crc = crc32(&bytes[16..]);
+        let database = format!("{crc:08X},3,0,V,0,0,0,ntsc,0,nes");
+        assert_eq!(crate::mapper::new(&bytes, &[], &database).unwrap().2, Corrections::MAPPER | Corrections::MIRRORING | Corrections::PRG_RAM);
+        bytes[7] = 0x08;
+        assert_eq!(crate::mapper::new(&bytes, &[], &database).unwrap().2, Corrections::empty());
+    }
+}
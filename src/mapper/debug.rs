@@ -0,0 +1,114 @@
+use super::*;
+use std::fmt::Write;
+
+// A page of the CPU or PPU address space and what it is mapped to.
+#[derive(Clone, Copy, Debug)]
+pub struct BankWindow {
+    pub addr: u16,
+    pub size: usize,
+    pub page: Option<Page>, // None when unmapped (open bus)
+}
+
+// Snapshot of a mapper for debuggers, see `Mapper::get_debug_state`.
+#[derive(Clone, Debug)]
+pub struct DebugState {
+    pub name: String,
+    pub mirroring: Mirroring,
+    pub prg: Vec<BankWindow>, // $6000-$FFFF
+    pub chr: Vec<BankWindow>, // $0000-$1FFF
+    pub registers: Vec<(String, u32)>,
+}
+
+impl DebugState {
+    pub fn new<M: Mapper + ?Sized>(mapper: &M) -> Self {
+        let cartridge = mapper.get_cartridge();
+        let prg = (0x6000..=0xFFFF).step_by(PRG_PAGE_SIZE)
+            .map(|addr| BankWindow { addr, size: PRG_PAGE_SIZE, page: cartridge.get_prg_page(addr) })
+            .collect();
+        let chr = (0x0000..0x2000).step_by(CHR_PAGE_SIZE)
+            .map(|addr| BankWindow { addr, size: CHR_PAGE_SIZE, page: cartridge.get_chr_page(addr) })
+            .collect();
+        DebugState {
+            name: mapper.to_string(),
+            mirroring: mapper.get_mirroring(),
+            prg,
+            chr,
+            registers: mapper.get_registers(),
+        }
+    }
+
+    // {"name":"MMC1","mirroring":"Vertical","prg":[{"addr":24576,"size":8192,"memory":"Ram","offset":0},...],
+    //  "chr":[...],"registers":{"control":12,...}}
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let windows = |json: &mut String, windows: &[BankWindow]| {
+            for (n, window) in windows.iter().enumerate() {
+                if n > 0 { json.push(','); }
+                let _ = write!(json, "{{\"addr\":{},\"size\":{}", window.addr, window.size);
+                match window.page {
+                    Some(page) => { let _ = write!(json, ",\"memory\":\"{:?}\",\"offset\":{}}}", page.memory, page.offset); },
+                    None => json.push_str(",\"memory\":null,\"offset\":null}"),
+                }
+            }
+        };
+        let _ = write!(json, "{{\"name\":\"{}\",\"mirroring\":\"{:?}\",\"prg\":[", escape(&self.name), self.mirroring);
+        windows(&mut json, &self.prg);
+        json.push_str("],\"chr\":[");
+        windows(&mut json, &self.chr);
+        json.push_str("],\"registers\":{");
+        for (n, (name, value)) in self.registers.iter().enumerate() {
+            if n > 0 { json.push(','); }
+            let _ = write!(json, "\"{}\":{}", escape(name), value);
+        }
+        json.push_str("}}");
+        json
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars().flat_map(|c| match c {
+        '"' | '\\' => vec!['\\', c],
+        c if c.is_control() => format!("\\u{:04x}", c as u32).chars().collect(),
+        c => vec![c],
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_strings() {
+        assert_eq!(escape(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape(r"C:\nes"), r"C:\\nes");
+        assert_eq!(escape("a\nb\t\u{1}\u{7F}"), r"a\u000ab\u0009\u0001\u007f");
+        assert_eq!(escape("ÜNROM ✓"), "ÜNROM ✓");
+    }
+
+    #[test]
+    fn json_shape() {
+        let state = DebugState {
+            name: "\"Odd\" \\ board\n".to_string(),
+            mirroring: Mirroring::Vertical,
+            prg: vec![
+                BankWindow { addr: 0x6000, size: 0x2000, page: None },
+                BankWindow { addr: 0x8000, size: 0x2000, page: Some(Page { memory: Memory::Rom, offset: 0x4000 }) },
+            ],
+            chr: vec![],
+            registers: vec![("bank".to_string(), 3), ("a\"b".to_string(), 0)],
+        };
+        assert_eq!(state.to_json(), concat!(
+            r#"{"name":"\"Odd\" \\ board\u000a","mirroring":"Vertical","#,
+            r#""prg":[{"addr":24576,"size":8192,"memory":null,"offset":null},"#,
+            r#"{"addr":32768,"size":8192,"memory":"Rom","offset":16384}],"#,
+            r#""chr":[],"registers":{"bank":3,"a\"b":0}}"#,
+        ));
+
+        // A real mapper: every page of both address spaces, no registers.
+        let header = test::header(0, 0x8000, 0x2000);
+        let json = NROM::new(&header, test::cartridge(&header)).get_debug_state().to_json();
+        assert!(json.starts_with(r#"{"name":"NROM","mirroring":"Horizontal","prg":[{"addr":24576,"#));
+        assert_eq!(json.matches(r#""addr":"#).count(), 5 + 8);
+        assert!(json.ends_with(r#""registers":{}}"#));
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+// Why a ROM couldn't be loaded. `code` is what the wasm exports return.
+#[derive(PartialEq, Clone, Debug)]
+pub enum LoadError {
+    UnknownFormat,
+    TruncatedHeader,
+    SizeMismatch { expected: usize, actual: usize },
+    BadTrainer,
+    UnsupportedMapper(u16),
+    UnsupportedBoard(String),
+    MissingBios,
+    BadDisk,
+    DiskFull,
+}
+
+impl LoadError {
+    pub fn code(&self) -> i32 {
+        match self {
+            LoadError::UnknownFormat => 1,
+            LoadError::TruncatedHeader => 2,
+            LoadError::SizeMismatch { .. } => 3,
+            LoadError::BadTrainer => 4,
+            LoadError::UnsupportedMapper(_) => 5,
+            LoadError::UnsupportedBoard(_) => 6,
+            LoadError::MissingBios => 7,
+            LoadError::BadDisk => 8,
+            LoadError::DiskFull => 9,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::UnknownFormat => write!(f, "Only NES, UNIF and FDS files supported."),
+            LoadError::TruncatedHeader => write!(f, "ROM header is truncated."),
+            LoadError::SizeMismatch { expected, actual } => write!(f, "ROM file is {actual} bytes, header expects {expected}."),
+            LoadError::BadTrainer => write!(f, "ROM trainer is truncated."),
+            LoadError::UnsupportedMapper(mapper) => write!(f, "Mapper {mapper} not implemented."),
+            LoadError::UnsupportedBoard(board) if board.is_empty() => write!(f, "UNIF board name missing."),
+            LoadError::UnsupportedBoard(board) => write!(f, "UNIF board {board} not implemented."),
+            LoadError::MissingBios => write!(f, "FDS BIOS (8KB) required."),
+            LoadError::BadDisk => write!(f, "FDS image has no disk sides."),
+            LoadError::DiskFull => write!(f, "FDS disk side is too full to save in its image format."),
+        }
+    }
+}
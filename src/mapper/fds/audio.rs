@@ -0,0 +1,206 @@
+// 2C33 sound: one wavetable channel with a frequency modulation unit.
+// https://www.nesdev.org/wiki/FDS_audio
+
+const MOD_TABLE: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1]; // Entry 4 resets the counter
+const MASTER_VOLUME: [u32; 4] = [36, 24, 17, 14]; // 2/2, 2/3, 2/4, 2/5
+
+struct Envelope {
+    speed: u8,
+    gain: u8,
+    increase: bool,
+    disabled: bool,
+    timer: u32,
+    frequency: u16,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Envelope { speed: 0, gain: 0, increase: false, disabled: true, timer: 0, frequency: 0 }
+    }
+
+    fn write(&mut self, reg: u16, value: u8, master_speed: u8) {
+        match reg {
+            0 => {
+                self.speed = value & 0x3F;
+                self.increase = value & 0x40 != 0;
+                self.disabled = value & 0x80 != 0;
+                self.reset_timer(master_speed);
+                if self.disabled { self.gain = self.speed; }
+            },
+            2 => self.frequency = (self.frequency & 0x0F00) | value as u16,
+            3 => self.frequency = (self.frequency & 0x00FF) | ((value & 0x0F) as u16) << 8,
+            _ => ()
+        }
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    fn tick(&mut self, master_speed: u8) -> bool {
+        if self.disabled || master_speed == 0 { return false }
+        if self.timer > 0 { self.timer -= 1; }
+        if self.timer == 0 {
+            self.reset_timer(master_speed);
+            if self.increase && self.gain < 32 { self.gain += 1; }
+            if !self.increase && self.gain > 0 { self.gain -= 1; }
+            return true
+        }
+        false
+    }
+}
+
+pub struct Audio {
+    wave_table: [u8; 0x40],
+    wave_write: bool,
+    wave_halt: bool,
+    wave_position: u8,
+    wave_accumulator: u32,
+    envelopes_halt: bool,
+    master_volume: u8,
+    master_speed: u8,
+    volume: Envelope,
+    modulation: Envelope,
+    mod_table: [u8; 0x40],
+    mod_position: u8,
+    mod_counter: i8, // 7-bit signed
+    mod_accumulator: u16,
+    mod_halt: bool,
+    mod_output: i32,
+    output: u8,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Audio {
+            wave_table: [0; 0x40],
+            wave_write: false,
+            wave_halt: true,
+            wave_position: 0,
+            wave_accumulator: 0,
+            envelopes_halt: false,
+            master_volume: 0,
+            master_speed: 0xE8,
+            volume: Envelope::new(),
+            modulation: Envelope::new(),
+            mod_table: [0; 0x40],
+            mod_position: 0,
+            mod_counter: 0,
+            mod_accumulator: 0,
+            mod_halt: true,
+            mod_output: 0,
+            output: 0,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x4040..=0x407F => self.wave_table[(addr - 0x4040) as usize] | 0x40,
+            0x4090 => self.volume.gain | 0x40,
+            0x4092 => self.modulation.gain | 0x40,
+            _ => 0
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4040..=0x407F => if self.wave_write { self.wave_table[(addr - 0x4040) as usize] = value & 0x3F },
+            0x4080 | 0x4082 => self.volume.write(addr - 0x4080, value, self.master_speed),
+            0x4083 => {
+                self.volume.write(3, value, self.master_speed);
+                self.wave_halt = value & 0x80 != 0;
+                self.envelopes_halt = value & 0x40 != 0;
+                if self.wave_halt { self.wave_position = 0; }
+                if self.envelopes_halt {
+                    self.volume.reset_timer(self.master_speed);
+                    self.modulation.reset_timer(self.master_speed);
+                }
+            },
+            0x4084 | 0x4086 => self.modulation.write(addr - 0x4084, value, self.master_speed),
+            0x4085 => self.set_mod_counter((value & 0x7F) as i32),
+            0x4087 => {
+                self.modulation.write(3, value, self.master_speed);
+                self.mod_halt = value & 0x80 != 0;
+                if self.mod_halt { self.mod_accumulator = 0; }
+            },
+            0x4088 => if self.mod_halt {
+                // Each write fills two consecutive entries.
+                self.mod_table[self.mod_position as usize] = value & 0x07;
+                self.mod_table[(self.mod_position as usize + 1) & 0x3F] = value & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            },
+            0x4089 => {
+                self.master_volume = value & 0x03;
+                self.wave_write = value & 0x80 != 0;
+            },
+            0x408A => self.master_speed = value,
+            _ => ()
+        }
+    }
+
+    fn set_mod_counter(&mut self, value: i32) {
+        // Wraps to -64..=63
+        self.mod_counter = (((value + 64) & 0x7F) - 64) as i8;
+    }
+
+    fn update_mod_output(&mut self) {
+        let mut temp = self.mod_counter as i32 * self.modulation.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.mod_counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 { temp -= 256; } else if temp < -64 { temp += 256; }
+        temp *= self.volume.frequency as i32;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 { temp += 1; }
+        self.mod_output = temp;
+    }
+
+    fn tick_modulator(&mut self) -> bool {
+        let frequency = self.modulation.frequency;
+        if self.mod_halt || frequency == 0 { return false }
+        let (accumulator, overflow) = self.mod_accumulator.overflowing_add(frequency);
+        self.mod_accumulator = accumulator;
+        if overflow {
+            match self.mod_table[self.mod_position as usize] {
+                4 => self.set_mod_counter(0),
+                entry => self.set_mod_counter(self.mod_counter as i32 + MOD_TABLE[entry as usize] as i32),
+            }
+            self.mod_position = (self.mod_position + 1) & 0x3F;
+        }
+        overflow
+    }
+
+    // Clocked every CPU cycle.
+    pub fn tick(&mut self) {
+        if !self.wave_halt && !self.envelopes_halt {
+            self.volume.tick(self.master_speed);
+            if self.modulation.tick(self.master_speed) { self.update_mod_output(); }
+        }
+        if self.tick_modulator() { self.update_mod_output(); }
+
+        if self.wave_halt {
+            self.wave_accumulator = 0;
+        } else if !self.wave_write {
+            let pitch = self.volume.frequency as i32 + self.mod_output;
+            if pitch > 0 {
+                self.wave_accumulator += pitch as u32;
+                if self.wave_accumulator > 0xFFFF {
+                    self.wave_accumulator &= 0xFFFF;
+                    self.wave_position = (self.wave_position + 1) & 0x3F;
+                }
+            }
+        }
+        // The output holds its last value while the wave table is writable.
+        if !self.wave_write {
+            let level = self.volume.gain.min(32) as u32 * MASTER_VOLUME[self.master_volume as usize];
+            self.output = ((self.wave_table[self.wave_position as usize] as u32 * level) / 1152) as u8;
+        }
+    }
+
+    pub fn output(&self) -> f32 {
+        self.output as f32 / 63.0
+    }
+}
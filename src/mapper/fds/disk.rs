@@ -0,0 +1,214 @@
+use crate::mapper::LoadError;
+
+// https://www.nesdev.org/wiki/FDS_disk_format
+const HEADER_SIZE: usize = 16;
+const SIDE_SIZE: usize = 65500; // .fds side (block data only)
+pub const QD_SIDE_SIZE: usize = 0x10000; // .qd side (block data + CRCs)
+
+// The drive sees the disk as a bit stream with gaps between blocks.
+pub const LEADING_GAP: usize = 28300 / 8;
+const BLOCK_GAP: usize = 976 / 8;
+const BLOCK_START: u8 = 0x80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Fds { header: bool },
+    Qd,
+}
+
+pub struct Disk {
+    format: Format,
+    sides: Vec<Vec<u8>>, // Raw sides (gaps, start marks, blocks and CRCs)
+}
+
+impl Disk {
+    pub fn is_disk(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"FDS\x1A") || bytes.get(1..15) == Some(b"*NINTENDO-HVC*")
+    }
+
+    pub fn new(bytes: &[u8]) -> Result<Self, LoadError> {
+        let (format, data) = if bytes.starts_with(b"FDS\x1A") {
+            (Format::Fds { header: true }, &bytes[HEADER_SIZE.min(bytes.len())..])
+        } else if bytes.len() % QD_SIDE_SIZE == 0 {
+            (Format::Qd, bytes)
+        } else {
+            (Format::Fds { header: false }, bytes)
+        };
+
+        let side_size = if format == Format::Qd { QD_SIDE_SIZE } else { SIDE_SIZE };
+        let sides: Vec<Vec<u8>> = data.chunks(side_size)
+            .filter(|side| side.len() == side_size)
+            .map(|side| Disk::add_gaps(side, format))
+            .collect();
+
+        if sides.is_empty() { return Err(LoadError::BadDisk) }
+        Ok(Disk { format, sides })
+    }
+
+    pub fn sides(&self) -> usize {
+        self.sides.len()
+    }
+
+    pub fn side(&self, side: usize) -> &[u8] {
+        &self.sides[side]
+    }
+
+    pub fn side_mut(&mut self, side: usize) -> &mut [u8] {
+        &mut self.sides[side]
+    }
+
+    // Length of the data part of a block, given its type byte.
+    // File data blocks (4) take their size from the preceding file header block (3).
+    fn block_length(block_type: u8, file_size: usize) -> Option<usize> {
+        match block_type {
+            1 => Some(56), // Disk info
+            2 => Some(2),  // File amount
+            3 => Some(16), // File header
+            4 => Some(1 + file_size), // File data
+            _ => None
+        }
+    }
+
+    fn add_gaps(side: &[u8], format: Format) -> Vec<u8> {
+        let crc_len = if format == Format::Qd { 2 } else { 0 };
+        let mut raw = vec![0; LEADING_GAP];
+        let mut file_size = 0;
+        let mut i = 0;
+        while i < side.len() {
+            let Some(len) = Disk::block_length(side[i], file_size) else { break };
+            if i + len + crc_len > side.len() { break }
+            if side[i] == 3 {
+                file_size = side[i + 13] as usize | (side[i + 14] as usize) << 8;
+            }
+            raw.push(BLOCK_START);
+            raw.extend_from_slice(&side[i..i + len]);
+            if crc_len == 0 {
+                // .fds images don't store CRCs, the BIOS doesn't check them when reading.
+                raw.extend_from_slice(&[0x4D, 0x62]);
+            } else {
+                raw.extend_from_slice(&side[i + len..i + len + crc_len]);
+            }
+            raw.extend(std::iter::repeat(0).take(BLOCK_GAP));
+            i += len + crc_len;
+        }
+        // Leave room for files written after the last block, sides already past that keep all their data.
+        let min_len = LEADING_GAP + QD_SIDE_SIZE + 64 * BLOCK_GAP;
+        if raw.len() < min_len { raw.resize(min_len, 0); }
+        raw
+    }
+
+    fn remove_gaps(raw: &[u8], format: Format) -> Result<Vec<u8>, LoadError> {
+        let (crc_len, side_size) = if format == Format::Qd { (2, QD_SIDE_SIZE) } else { (0, SIDE_SIZE) };
+        let mut side = Vec::with_capacity(side_size);
+        let mut file_size = 0;
+        let mut i = 0;
+        loop {
+            while i < raw.len() && raw[i] == 0 { i += 1; }
+            if i >= raw.len() || raw[i] != BLOCK_START { break }
+            i += 1;
+            let Some(len) = raw.get(i).and_then(|&t| Disk::block_length(t, file_size)) else { break };
+            if i + len + 2 > raw.len() { break }
+            if raw[i] == 3 {
+                file_size = raw[i + 13] as usize | (raw[i + 14] as usize) << 8;
+            }
+            side.extend_from_slice(&raw[i..i + len + crc_len]);
+            i += len + 2;
+        }
+        // Files written past the end of the side don't fit back in the image.
+        if side.len() > side_size { return Err(LoadError::DiskFull) }
+        side.resize(side_size, 0);
+        Ok(side)
+    }
+
+    // Rebuilds the image in the format it was loaded from.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, LoadError> {
+        let mut bytes = Vec::new();
+        if let Format::Fds { header: true } = self.format {
+            bytes.extend_from_slice(b"FDS\x1A");
+            bytes.push(self.sides.len() as u8);
+            bytes.resize(HEADER_SIZE, 0);
+        }
+        for raw in self.sides.iter() {
+            bytes.extend(Disk::remove_gaps(raw, self.format)?);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Disk info, file amount, then `files` files of `size` bytes each.
+    fn side(files: usize, size: usize) -> Vec<u8> {
+        let mut side = vec![1];
+        side.extend_from_slice(b"*NINTENDO-HVC*");
+        side.resize(56, 0);
+        side.extend_from_slice(&[2, files as u8]);
+        for file in 0..files {
+            let mut header = [0; 16];
+            header[0] = 3;
+            header[1] = file as u8;
+            header[13] = size as u8;
+            header[14] = (size >> 8) as u8;
+            side.extend_from_slice(&header);
+            side.push(4);
+            side.extend(std::iter::repeat(file as u8).take(size));
+        }
+        side.resize(SIDE_SIZE, 0);
+        side
+    }
+
+    #[test]
+    fn round_trip() {
+        let side = side(4, 0x100);
+        let disk = Disk::new(&side).unwrap();
+        assert_eq!(disk.sides(), 1);
+        assert_eq!(disk.to_bytes().unwrap(), side);
+    }
+
+    #[test]
+    fn many_files_are_not_truncated() {
+        // 200 small files, with their gaps the raw side is longer than the room left after the last block.
+        let side = side(200, 100);
+        let disk = Disk::new(&side).unwrap();
+        assert!(disk.side(0).len() > LEADING_GAP + QD_SIDE_SIZE + 64 * BLOCK_GAP);
+        assert_eq!(disk.to_bytes().unwrap(), side);
+    }
+
+    #[test]
+    fn fds_header() {
+        let mut bytes = b"FDS\x1A\x02".to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend(side(1, 16));
+        bytes.extend(side(2, 16));
+        let disk = Disk::new(&bytes).unwrap();
+        assert_eq!(disk.sides(), 2);
+        assert_eq!(disk.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn full_side() {
+        let mut disk = Disk::new(&side(1, 16)).unwrap();
+        // The game fills the rest of the side with a file bigger than the image has room for.
+        let raw = disk.side_mut(0);
+        let end = raw.iter().rposition(|&byte| byte != 0).unwrap() + 3 + BLOCK_GAP;
+        let size = SIDE_SIZE - 56 - 2 - 17;
+        let mut header = [0; 16];
+        header[0] = 3;
+        header[13] = size as u8;
+        header[14] = (size >> 8) as u8;
+        raw[end] = BLOCK_START;
+        raw[end + 1..end + 17].copy_from_slice(&header);
+        let end = end + 19 + BLOCK_GAP;
+        raw[end] = BLOCK_START;
+        raw[end + 1] = 4;
+        assert!(matches!(disk.to_bytes(), Err(LoadError::DiskFull)));
+    }
+
+    #[test]
+    fn bad_disk() {
+        assert!(Disk::is_disk(&side(1, 16)));
+        assert!(matches!(Disk::new(&[0; 100]), Err(LoadError::BadDisk)));
+    }
+}
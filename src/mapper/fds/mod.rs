@@ -0,0 +1,520 @@
+mod audio;
+mod disk;
+
+use std::fmt;
+use super::*;
+use self::{ audio::Audio, disk::Disk };
+
+pub const BIOS_SIZE: usize = 0x2000;
+
+// CPU cycles the drive takes to transfer a byte (~96.4 kbit/s).
+const BYTE_DELAY: usize = 150;
+// CPU cycles for the head to return to the start of the disk.
+const REWIND_DELAY: usize = 50000;
+
+// Famicom Disk System (RAM adapter + disk drive).
+// https://www.nesdev.org/wiki/Family_Computer_Disk_System
+#[allow(clippy::upper_case_acronyms)]
+pub struct FDS {
+    cartridge: Cartridge, // BIOS as PRG ROM, 32KB PRG RAM and 8KB CHR RAM
+    mirroring: Mirroring,
+    audio: Audio,
+    disk: Disk,
+    side: Option<usize>,
+    // Registers
+    disk_enabled: bool,
+    sound_enabled: bool,
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_repeat: bool,
+    timer_enabled: bool,
+    timer_irq: bool,
+    read_data: u8,
+    write_data: u8,
+    // Drive
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    previous_crc_control: bool,
+    disk_ready: bool,
+    disk_irq_enabled: bool,
+    disk_irq: bool,
+    transfer_complete: bool,
+    scanning: bool,
+    end_of_head: bool,
+    gap_ended: bool,
+    position: usize,
+    delay: usize,
+    crc: u16,
+}
+
+impl fmt::Display for FDS {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FDS")
+    }
+}
+
+impl FDS {
+    pub fn is_disk(bytes: &[u8]) -> bool {
+        Disk::is_disk(bytes)
+    }
+
+    pub fn new(bytes: &[u8], bios: &[u8]) -> Result<Self, LoadError> {
+        if bios.len() != BIOS_SIZE { return Err(LoadError::MissingBios) }
+        let disk = Disk::new(bytes)?;
+        let mut cartridge = Cartridge::new(bios.to_vec(), Vec::new(), 0x8000, 0x2000, false);
+        cartridge.map_prg(0x6000, 0x8000, Memory::Ram, 0);
+        cartridge.map_prg(0xE000, BIOS_SIZE, Memory::Rom, 0);
+        cartridge.map_chr(0x0000, 0x2000, Memory::Ram, 0);
+        Ok(FDS {
+            cartridge,
+            mirroring: Mirroring::Horizontal,
+            audio: Audio::new(),
+            disk,
+            side: Some(0),
+            disk_enabled: true,
+            sound_enabled: true,
+            timer_reload: 0,
+            timer_counter: 0,
+            timer_repeat: false,
+            timer_enabled: false,
+            timer_irq: false,
+            read_data: 0,
+            write_data: 0,
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            previous_crc_control: false,
+            disk_ready: false,
+            disk_irq_enabled: false,
+            disk_irq: false,
+            transfer_complete: false,
+            scanning: false,
+            end_of_head: true,
+            gap_ended: false,
+            position: 0,
+            delay: 0,
+            crc: 0,
+        })
+    }
+
+    pub fn get_disk_sides(&self) -> usize {
+        self.disk.sides()
+    }
+
+    pub fn get_disk_side(&self) -> Option<usize> {
+        self.side
+    }
+
+    // Swapping disks stops any transfer, the head starts over from the beginning of the new side.
+    pub fn insert_disk(&mut self, side: usize) {
+        if side < self.disk.sides() {
+            self.side = Some(side);
+            self.stop_drive();
+        }
+    }
+
+    pub fn eject_disk(&mut self) {
+        self.side = None;
+        self.stop_drive();
+    }
+
+    fn stop_drive(&mut self) {
+        self.end_of_head = true;
+        self.scanning = false;
+        self.position = 0;
+    }
+
+    // Disk image (in the format it was loaded from) including any writes made by the game.
+    pub fn get_disk_image(&self) -> Result<Vec<u8>, LoadError> {
+        self.disk.to_bytes()
+    }
+
+    fn update_crc(&mut self, value: u8) {
+        // CRC-16/KERMIT, fed LSB first like the RAM adapter does.
+        for n in 0..8 {
+            let carry = (self.crc & 1) ^ ((value >> n) & 1) as u16;
+            self.crc >>= 1;
+            if carry == 1 { self.crc ^= 0x8408; }
+        }
+    }
+
+    fn tick_timer(&mut self) {
+        if !self.timer_enabled { return }
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            if !self.timer_repeat { self.timer_enabled = false; }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    fn tick_drive(&mut self) {
+        let side = match self.side {
+            Some(side) if self.motor_on => side,
+            _ => return self.stop_drive()
+        };
+        if self.reset_transfer && !self.scanning { return }
+        if self.end_of_head {
+            self.delay = REWIND_DELAY;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return
+        }
+        if self.delay > 0 { self.delay -= 1; return }
+
+        self.scanning = true;
+        let mut irq = self.disk_irq_enabled;
+        if self.read_mode {
+            let data = self.disk.side(side)[self.position];
+            if !self.previous_crc_control { self.update_crc(data); }
+            if !self.disk_ready {
+                self.gap_ended = false;
+                self.crc = 0;
+            } else if data != 0 && !self.gap_ended {
+                // Start mark, the next byte is the first of the block.
+                self.gap_ended = true;
+                irq = false;
+            }
+            if self.gap_ended {
+                self.transfer_complete = true;
+                self.read_data = data;
+                if irq { self.disk_irq = true; }
+            }
+        } else {
+            let mut data = 0;
+            if !self.crc_control {
+                self.transfer_complete = true;
+                data = self.write_data;
+                if irq { self.disk_irq = true; }
+            }
+            if !self.disk_ready { data = 0; }
+            if !self.crc_control {
+                self.update_crc(data);
+            } else {
+                // `crc` already holds the CRC of the block, low byte first.
+                data = (self.crc & 0xFF) as u8;
+                self.crc >>= 8;
+            }
+            // The head writes a couple of bytes behind where it reads.
+            let position = self.position.saturating_sub(2);
+            self.disk.side_mut(side)[position] = data;
+            self.gap_ended = false;
+        }
+        self.previous_crc_control = self.crc_control;
+
+        self.position += 1;
+        if self.position >= self.disk.side(side).len() {
+            self.motor_on = false;
+            self.end_of_head = true;
+        } else {
+            self.delay = BYTE_DELAY;
+        }
+    }
+
+    fn read_disk_register(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4030 => {
+                let mut value = 0;
+                if self.timer_irq { value |= 0x01; }
+                if self.transfer_complete { value |= 0x02; }
+                if self.end_of_head { value |= 0x40; }
+                self.transfer_complete = false;
+                self.timer_irq = false;
+                self.disk_irq = false;
+                value
+            },
+            0x4031 => {
+                self.transfer_complete = false;
+                self.disk_irq = false;
+                self.read_data
+            },
+            0x4032 => {
+                let mut value = 0x40;
+                if self.side.is_none() { value |= 0x01 | 0x04; } // Not inserted, write protected
+                if self.side.is_none() || !self.scanning { value |= 0x02; } // Not ready
+                value
+            },
+            0x4033 => 0x80, // Battery good
+            _ => 0
+        }
+    }
+
+    fn write_disk_register(&mut self, addr: u16, val: u8) {
+        if addr == 0x4023 {
+            self.disk_enabled = val & 0x01 != 0;
+            self.sound_enabled = val & 0x02 != 0;
+            if !self.disk_enabled {
+                self.timer_enabled = false;
+                self.timer_irq = false;
+            }
+            return
+        }
+        if !self.disk_enabled { return }
+        match addr {
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | val as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | (val as u16) << 8,
+            0x4022 => {
+                self.timer_repeat = val & 0x01 != 0;
+                self.timer_enabled = val & 0x02 != 0;
+                if self.timer_enabled {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            },
+            0x4024 => {
+                self.write_data = val;
+                self.transfer_complete = false;
+                self.disk_irq = false;
+            },
+            0x4025 => {
+                self.motor_on = val & 0x01 != 0;
+                self.reset_transfer = val & 0x02 != 0;
+                self.read_mode = val & 0x04 != 0;
+                self.mirroring = if val & 0x08 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+                self.crc_control = val & 0x10 != 0;
+                self.disk_ready = val & 0x40 != 0;
+                self.disk_irq_enabled = val & 0x80 != 0;
+                self.disk_irq = false;
+            },
+            _ => ()
+        }
+    }
+}
+
+impl Mapper for FDS {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        vec![
+            ("timer_reload".to_string(), self.timer_reload as u32),
+            ("timer_counter".to_string(), self.timer_counter as u32),
+            ("timer_enabled".to_string(), self.timer_enabled as u32),
+            ("timer_irq".to_string(), self.timer_irq as u32),
+            ("disk_irq".to_string(), self.disk_irq as u32),
+            ("motor_on".to_string(), self.motor_on as u32),
+            ("side".to_string(), self.side.map_or(u32::MAX, |side| side as u32)),
+            ("position".to_string(), self.position as u32),
+        ]
+    }
+
+    fn read_prg(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4030..=0x4033 => self.read_disk_register(addr),
+            0x4040..=0x4097 => self.audio.read(addr),
+            _ => self.cartridge.read_prg(addr)
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x4020..=0x4026 => self.write_disk_register(addr, val),
+            0x4040..=0x408A => if self.sound_enabled { self.audio.write(addr, val) },
+            _ => self.cartridge.write_prg(addr, val)
+        }
+    }
+
+    fn tick(&mut self) {
+        self.tick_timer();
+        self.tick_drive();
+        self.audio.tick();
+    }
+
+    fn irq(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn get_audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn as_fds(&mut self) -> Option<&mut FDS> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::disk::{ LEADING_GAP, QD_SIDE_SIZE };
+
+    // CRC-16/KERMIT of a block, start mark included.
+    fn crc(block: &[u8]) -> [u8; 2] {
+        let mut crc = 0u16;
+        for byte in std::iter::once(&0x80).chain(block) {
+            crc ^= *byte as u16;
+            for _ in 0..8 { crc = if crc & 1 != 0 { crc >> 1 ^ 0x8408 } else { crc >> 1 }; }
+        }
+        crc.to_le_bytes()
+    }
+
+    // .qd side (blocks followed by their CRCs): disk info with the side number, then `files` files.
+    fn side(number: u8, files: usize) -> Vec<u8> {
+        let mut info = vec![1];
+        info.extend_from_slice(b"*NINTENDO-HVC*");
+        info.resize(56, 0);
+        info[21] = number;
+        let mut blocks = vec![info, vec![2, files as u8]];
+        for file in 0..files {
+            let mut header = vec![0; 16];
+            header[0] = 3;
+            header[1] = file as u8;
+            header[13] = 100;
+            blocks.push(header);
+            blocks.push([4].into_iter().chain(std::iter::repeat(file as u8).take(100)).collect());
+        }
+        let mut side: Vec<u8> = blocks.iter().flat_map(|block| [&block[..], &crc(block)].concat()).collect();
+        side.resize(QD_SIDE_SIZE, 0);
+        side
+    }
+
+    fn fds(sides: &[Vec<u8>]) -> FDS {
+        FDS::new(&sides.concat(), &[0; BIOS_SIZE]).unwrap()
+    }
+
+    // Next byte the drive transfers, through $4031.
+    fn read(fds: &mut FDS) -> u8 {
+        for _ in 0..1_000_000 {
+            fds.tick();
+            if fds.transfer_complete { return fds.read_prg(0x4031) }
+        }
+        panic!("no transfer");
+    }
+
+    // Next byte the drive transfers, through $4024.
+    fn write(fds: &mut FDS, val: u8) {
+        fds.write_prg(0x4024, val);
+        for _ in 0..1_000_000 {
+            fds.tick();
+            if fds.transfer_complete { return }
+        }
+        panic!("no transfer");
+    }
+
+    #[test]
+    fn timer_irq() {
+        let mut fds = fds(&[side(0, 0)]);
+        fds.write_prg(0x4020, 0x10);
+        fds.write_prg(0x4021, 0x00);
+        // Repeating, fires every reload + 1 cycles until acknowledged through $4030.
+        fds.write_prg(0x4022, 0x03);
+        for _ in 0..2 {
+            for _ in 0..0x10 { fds.tick(); }
+            assert!(!fds.irq());
+            fds.tick();
+            assert!(fds.irq());
+            assert_eq!(fds.read_prg(0x4030) & 0x01, 0x01);
+            assert!(!fds.irq());
+            assert_eq!(fds.read_prg(0x4030) & 0x01, 0);
+        }
+        // One shot, the timer stops after firing.
+        fds.write_prg(0x4022, 0x02);
+        for _ in 0..0x11 { fds.tick(); }
+        assert!(fds.irq());
+        fds.read_prg(0x4030);
+        for _ in 0..0x100 { fds.tick(); }
+        assert!(!fds.irq());
+        // Disabling the disk registers stops it and drops the IRQ.
+        fds.write_prg(0x4022, 0x03);
+        for _ in 0..0x11 { fds.tick(); }
+        assert!(fds.irq());
+        fds.write_prg(0x4023, 0x00);
+        assert!(!fds.irq());
+        for _ in 0..0x100 { fds.tick(); }
+        assert!(!fds.irq());
+    }
+
+    #[test]
+    fn block_read() {
+        let side = side(0, 0);
+        let mut fds = fds(&[side.clone(), side.clone()]);
+        // Motor on, read mode, ready and IRQ on transfer.
+        fds.write_prg(0x4025, 0xC5);
+        // The start mark ends the gap without an IRQ.
+        assert_eq!(read(&mut fds), 0x80);
+        assert!(!fds.irq());
+        while !fds.irq() { fds.tick(); }
+        assert_eq!(fds.read_prg(0x4031), side[0]);
+        assert!(!fds.irq());
+        for &byte in &side[1..56] {
+            assert_eq!(read(&mut fds), byte);
+        }
+        // Reading the stored CRC through the check leaves nothing over.
+        assert_eq!(read(&mut fds), side[56]);
+        assert_eq!(read(&mut fds), side[57]);
+        assert_eq!(fds.crc, 0);
+
+        // A damaged byte is caught.
+        fds.disk.side_mut(1)[LEADING_GAP + 10] ^= 0x01;
+        fds.insert_disk(1);
+        assert_eq!(read(&mut fds), 0x80);
+        for _ in 0..58 { read(&mut fds); }
+        assert_ne!(fds.crc, 0);
+    }
+
+    #[test]
+    fn block_write() {
+        let mut fds = fds(&[side(0, 0)]);
+        // Motor on, write mode, not ready yet: the gap is written as zeros.
+        fds.write_prg(0x4025, 0x01);
+        for _ in 0..0x10 { write(&mut fds, 0xFF); }
+        // The head writes two bytes behind where it reads.
+        let start = fds.position - 2;
+        fds.write_prg(0x4025, 0x41);
+        for val in [0x80, 0x02, 0x05] { write(&mut fds, val); }
+        // CRC control appends the CRC.
+        fds.write_prg(0x4025, 0x51);
+        let position = fds.position;
+        while fds.position < position + 2 { fds.tick(); }
+        fds.write_prg(0x4025, 0x00);
+        fds.tick();
+        fds.read_prg(0x4030);
+
+        let side = fds.disk.side(0);
+        assert!(side[..start].iter().all(|&byte| byte == 0));
+        assert_eq!(side[start..start + 3], [0x80, 0x02, 0x05]);
+        assert_eq!(side[start + 3..start + 5], crc(&[0x02, 0x05]));
+
+        // And it reads back with a good CRC.
+        fds.write_prg(0x4025, 0x45);
+        assert_eq!(read(&mut fds), 0x80);
+        assert_eq!(read(&mut fds), 0x02);
+        assert_eq!(read(&mut fds), 0x05);
+        read(&mut fds);
+        read(&mut fds);
+        assert_eq!(fds.crc, 0);
+    }
+
+    #[test]
+    fn switch_sides_mid_read() {
+        // Side A has enough files to be longer than side B.
+        let mut fds = fds(&[side(0, 250), side(1, 0)]);
+        assert!(fds.disk.side(0).len() > fds.disk.side(1).len());
+        fds.write_prg(0x4025, 0x45);
+        for _ in 0..4 { read(&mut fds); }
+        // Well past the end of side B when it goes in.
+        fds.position = fds.disk.side(1).len() + 100;
+        fds.insert_disk(1);
+        assert!(fds.end_of_head);
+        assert_eq!(fds.position, 0);
+        assert_eq!(fds.read_prg(0x4032) & 0x02, 0x02);
+        // The head starts over on the new side.
+        assert_eq!(read(&mut fds), 0x80);
+        let info: Vec<u8> = (0..56).map(|_| read(&mut fds)).collect();
+        assert_eq!(info[21], 1);
+
+        fds.position = 100;
+        fds.eject_disk();
+        assert!(fds.end_of_head);
+        assert_eq!(fds.position, 0);
+        for _ in 0..1000 { fds.tick(); }
+    }
+}
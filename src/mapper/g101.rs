@@ -0,0 +1,126 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_1: usize = 0x400;
+
+// Irem G-101 (mapper 32). Submapper 1 (Major League) has one-screen mirroring and no PRG mode.
+// https://www.nesdev.org/wiki/INES_Mapper_032
+pub struct G101 {
+    major_league: bool,
+    prg_mode: bool,
+    prg_banks: [u8; 2],
+    chr_banks: [u8; 8],
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for G101 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "G-101")
+    }
+}
+
+impl G101 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let major_league = header.submapper == 1;
+        let mut g101 = G101 {
+            major_league,
+            prg_mode: false,
+            prg_banks: [0, 1],
+            chr_banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            cartridge,
+            mirroring: if major_league { Mirroring::OneScreenLower } else { header.mirroring },
+        };
+        g101.update_banks();
+        g101
+    }
+
+    fn update_banks(&mut self) {
+        // PRG mode 1 swaps $8000 and $C000.
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        let (bank_8000, bank_c000) = if self.prg_mode {
+            (last.saturating_sub(1), self.prg_banks[0] as usize)
+        } else {
+            (self.prg_banks[0] as usize, last.saturating_sub(1))
+        };
+        self.cartridge.map_prg(0x6000, PRG_BANK_SIZE_8, Memory::Ram, 0);
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, bank_8000);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[1] as usize);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, bank_c000);
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+
+        let chr = self.cartridge.chr_memory();
+        for (n, &bank) in self.chr_banks.iter().enumerate() {
+            self.cartridge.map_chr(n as u16 * 0x400, CHR_BANK_SIZE_1, chr, bank as usize);
+        }
+    }
+}
+
+impl Mapper for G101 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers = vec![("prg_mode".to_string(), self.prg_mode as u32)];
+        registers.extend(self.prg_banks.iter().enumerate().map(|(n, &bank)| (format!("prg_{n}"), bank as u32)));
+        registers.extend(self.chr_banks.iter().enumerate().map(|(n, &bank)| (format!("chr_{n}"), bank as u32)));
+        registers
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr & 0xF000 {
+            0x6000 | 0x7000 => { self.cartridge.write_prg(addr, val); return },
+            0x8000 => self.prg_banks[0] = val & 0x1F,
+            0x9000 if !self.major_league => {
+                self.mirroring = if val & 0x01 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+                self.prg_mode = val & 0x02 != 0;
+            },
+            0xA000 => self.prg_banks[1] = val & 0x1F,
+            0xB000 => self.chr_banks[(addr & 0x07) as usize] = val,
+            _ => return
+        }
+        self.update_banks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    #[test]
+    fn banks_and_prg_mode() {
+        let mut header = header(32, 0x20000, 0x20000);
+        header.prg_ram_size = 0x2000;
+        let mut g101 = G101::new(&header, cartridge(&header));
+        g101.write_prg(0x8000, 3);
+        g101.write_prg(0xA000, 0x25);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| g101.read_prg(addr)), [3, 5, 14, 15]);
+        // Mode 1 swaps $8000 and $C000.
+        g101.write_prg(0x9000, 0x03);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| g101.read_prg(addr)), [14, 5, 3, 15]);
+        assert_eq!(g101.get_mirroring(), Mirroring::Horizontal);
+        g101.write_prg(0x9000, 0x00);
+        assert_eq!(g101.get_mirroring(), Mirroring::Vertical);
+
+        for n in 0..8 { g101.write_prg(0xB000 + n, 0x70 + n as u8); }
+        assert_eq!((g101.read_chr(0x0000), g101.read_chr(0x1C00)), (0x70, 0x77));
+
+        g101.write_prg(0x6000, 0x42);
+        assert_eq!(g101.read_prg(0x6000), 0x42);
+    }
+
+    #[test]
+    fn major_league() {
+        let mut header = header(32, 0x20000, 0x20000);
+        header.submapper = 1;
+        let mut g101 = G101::new(&header, cartridge(&header));
+        assert_eq!(g101.get_mirroring(), Mirroring::OneScreenLower);
+        // $9000 isn't connected.
+        g101.write_prg(0x9000, 0x03);
+        assert_eq!((g101.get_mirroring(), g101.read_prg(0x8000)), (Mirroring::OneScreenLower, 0));
+    }
+}
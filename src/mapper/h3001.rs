@@ -0,0 +1,172 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_1: usize = 0x400;
+
+// Irem H3001 (mapper 65), with a 16-bit IRQ counter clocked by the CPU.
+// https://www.nesdev.org/wiki/INES_Mapper_065
+pub struct H3001 {
+    prg_mode: bool,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq: bool,
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for H3001 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "H3001")
+    }
+}
+
+impl H3001 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut h3001 = H3001 {
+            prg_mode: false,
+            prg_banks: [0x00, 0x01, 0xFE],
+            chr_banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq: false,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        h3001.update_banks();
+        h3001
+    }
+
+    fn update_banks(&mut self) {
+        // PRG mode 1 swaps $8000 and $C000.
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        let (bank_8000, bank_c000) = if self.prg_mode { (2, 0) } else { (0, 2) };
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[bank_8000] as usize);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[1] as usize);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[bank_c000] as usize);
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+
+        let chr = self.cartridge.chr_memory();
+        for (n, &bank) in self.chr_banks.iter().enumerate() {
+            self.cartridge.map_chr(n as u16 * 0x400, CHR_BANK_SIZE_1, chr, bank as usize);
+        }
+    }
+}
+
+impl Mapper for H3001 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers = vec![("prg_mode".to_string(), self.prg_mode as u32)];
+        registers.extend(self.prg_banks.iter().enumerate().map(|(n, &bank)| (format!("prg_{n}"), bank as u32)));
+        registers.extend(self.chr_banks.iter().enumerate().map(|(n, &bank)| (format!("chr_{n}"), bank as u32)));
+        registers.extend([
+            ("irq_reload".to_string(), self.irq_reload as u32),
+            ("irq_counter".to_string(), self.irq_counter as u32),
+            ("irq_enabled".to_string(), self.irq_enabled as u32),
+            ("irq".to_string(), self.irq as u32),
+        ]);
+        registers
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000 => self.prg_banks[0] = val,
+            0x9000 => self.prg_mode = val & 0x80 != 0,
+            0x9001 => self.mirroring = if val & 0x80 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical },
+            0x9003 => {
+                self.irq_enabled = val & 0x80 != 0;
+                self.irq = false;
+            },
+            0x9004 => {
+                self.irq_counter = self.irq_reload;
+                self.irq = false;
+            },
+            0x9005 => self.irq_reload = (self.irq_reload & 0x00FF) | (val as u16) << 8,
+            0x9006 => self.irq_reload = (self.irq_reload & 0xFF00) | val as u16,
+            0xA000 => self.prg_banks[1] = val,
+            0xB000..=0xB007 => self.chr_banks[(addr & 0x07) as usize] = val,
+            0xC000 => self.prg_banks[2] = val,
+            _ => return
+        }
+        self.update_banks();
+    }
+
+    // Counts down to 0, fires once and stops there.
+    fn tick(&mut self) {
+        if self.irq_enabled && self.irq_counter > 0 {
+            self.irq_counter -= 1;
+            if self.irq_counter == 0 { self.irq = true; }
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn h3001() -> H3001 {
+        let header = header(65, 0x20000, 0x20000);
+        H3001::new(&header, cartridge(&header))
+    }
+
+    #[test]
+    fn banks_and_prg_mode() {
+        let mut h3001 = h3001();
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| h3001.read_prg(addr)), [0, 1, 14, 15]);
+        h3001.write_prg(0x8000, 2);
+        h3001.write_prg(0xA000, 3);
+        h3001.write_prg(0xC000, 4);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| h3001.read_prg(addr)), [2, 3, 4, 15]);
+        h3001.write_prg(0x9000, 0x80);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| h3001.read_prg(addr)), [4, 3, 2, 15]);
+
+        h3001.write_prg(0x9001, 0x80);
+        assert_eq!(h3001.get_mirroring(), Mirroring::Horizontal);
+        h3001.write_prg(0x9001, 0x00);
+        assert_eq!(h3001.get_mirroring(), Mirroring::Vertical);
+
+        h3001.write_prg(0xB003, 0x55);
+        assert_eq!(h3001.read_chr(0x0C00), 0x55);
+    }
+
+    #[test]
+    fn irq() {
+        let mut h3001 = h3001();
+        h3001.write_prg(0x9005, 0x00);
+        h3001.write_prg(0x9006, 0x03);
+        h3001.write_prg(0x9004, 0);
+        // Stopped until enabled.
+        h3001.tick();
+        assert_eq!(h3001.irq_counter, 3);
+
+        h3001.write_prg(0x9003, 0x80);
+        h3001.tick();
+        h3001.tick();
+        assert!(!h3001.irq());
+        h3001.tick();
+        assert!(h3001.irq());
+        // Stays at 0 without firing again.
+        h3001.write_prg(0x9003, 0x80);
+        for _ in 0..10 { h3001.tick(); }
+        assert!(!h3001.irq());
+        assert_eq!(h3001.irq_counter, 0);
+
+        // $9004 reloads and acknowledges.
+        h3001.write_prg(0x9005, 0x01);
+        h3001.write_prg(0x9004, 0);
+        assert_eq!(h3001.irq_counter, 0x0103);
+    }
+}
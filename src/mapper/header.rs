@@ -0,0 +1,255 @@
+use super::{ Mirroring, LoadError };
+
+// https://www.nesdev.org/wiki/NES_2.0
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Console {
+    Nes,
+    VsSystem { ppu: u8, hardware: u8 },
+    Playchoice,
+    Extended(u8),
+}
+
+#[derive(Clone, Copy)]
+pub struct Header {
+    pub nes2: bool,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub trainer: bool,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub prg_offset: usize,
+    pub chr_offset: usize,
+    pub timing: Timing,
+    pub console: Console,
+    pub misc_roms: u8,
+    pub expansion: u8,
+}
+
+impl Header {
+    pub fn new(bytes: &[u8]) -> Result<Self, LoadError> {
+        if !bytes.starts_with(b"NES\x1A") { return Err(LoadError::UnknownFormat) }
+        if bytes.len() < HEADER_SIZE { return Err(LoadError::TruncatedHeader) }
+        let nes2 = bytes[7] & 0x0C == 0x08;
+
+        let four_screen = bytes[6] & 0x8 != 0;
+        let vertical_mirroring = bytes[6] & 0x1 != 0;
+        let mirroring = match (four_screen, vertical_mirroring) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+        let battery = bytes[6] & 0x02 != 0;
+        let trainer = bytes[6] & 0x04 != 0;
+
+        let mut header = Header {
+            nes2,
+            mapper: ((bytes[6] & 0xF0) >> 4) as u16,
+            submapper: 0,
+            mirroring,
+            battery,
+            trainer,
+            prg_rom_size: bytes[4] as usize * 0x4000,
+            chr_rom_size: bytes[5] as usize * 0x2000,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            prg_offset: 0,
+            chr_offset: 0,
+            timing: Timing::Ntsc,
+            console: Console::Nes,
+            misc_roms: 0,
+            expansion: 0,
+        };
+
+        if nes2 {
+            header.parse_nes2(bytes);
+        } else {
+            header.parse_ines(bytes);
+        }
+
+        if trainer && bytes.len() < HEADER_SIZE + TRAINER_SIZE { return Err(LoadError::BadTrainer) }
+        header.prg_offset = HEADER_SIZE + if trainer { TRAINER_SIZE } else { 0 };
+        header.chr_offset = header.prg_offset.saturating_add(header.prg_rom_size);
+        // Trailing data (title, PlayChoice ROMs) is allowed, missing data isn't.
+        let expected = header.chr_offset.saturating_add(header.chr_rom_size);
+        if bytes.len() < expected { return Err(LoadError::SizeMismatch { expected, actual: bytes.len() }) }
+        Ok(header)
+    }
+
+    // iNES 1.0: RAM sizes aren't stored, assume the common 8KB.
+    fn parse_ines(&mut self, bytes: &[u8]) {
+        // Old dumping tools wrote signatures ("DiskDude!") over bytes 7-15.
+        if bytes[12..16].iter().all(|&b| b == 0) {
+            self.mapper |= (bytes[7] & 0xF0) as u16;
+            // The PPU isn't stored: without a database entry VS. games get the 2C03 (RGB palette),
+            // right for 2C03/2C05 boards, games on a 2C04 need an entry or an NES 2.0 header.
+            self.console = match bytes[7] & 0x03 {
+                1 => Console::VsSystem { ppu: 0, hardware: 0 },
+                2 => Console::Playchoice,
+                _ => Console::Nes,
+            };
+            if bytes[9] & 0x01 != 0 { self.timing = Timing::Pal; }
+        }
+        let prg_ram_size = 0x2000 * (bytes[8].max(1) as usize);
+        if self.battery { self.prg_nvram_size = prg_ram_size; } else { self.prg_ram_size = prg_ram_size; }
+        if self.chr_rom_size == 0 { self.chr_ram_size = 0x2000; }
+    }
+
+    fn parse_nes2(&mut self, bytes: &[u8]) {
+        self.mapper |= (bytes[7] & 0xF0) as u16 | ((bytes[8] & 0x0F) as u16) << 8;
+        self.submapper = bytes[8] >> 4;
+        self.prg_rom_size = Header::rom_size(bytes[4], bytes[9] & 0x0F, 0x4000);
+        self.chr_rom_size = Header::rom_size(bytes[5], bytes[9] >> 4, 0x2000);
+        self.prg_ram_size = Header::ram_size(bytes[10] & 0x0F);
+        self.prg_nvram_size = Header::ram_size(bytes[10] >> 4);
+        self.chr_ram_size = Header::ram_size(bytes[11] & 0x0F);
+        self.chr_nvram_size = Header::ram_size(bytes[11] >> 4);
+        self.timing = match bytes[12] & 0x03 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+        self.console = match bytes[7] & 0x03 {
+            0 => Console::Nes,
+            1 => Console::VsSystem { ppu: bytes[13] & 0x0F, hardware: bytes[13] >> 4 },
+            2 => Console::Playchoice,
+            _ => Console::Extended(bytes[13] & 0x0F),
+        };
+        self.misc_roms = bytes[14] & 0x03;
+        self.expansion = bytes[15] & 0x3F;
+    }
+
+    fn rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+        if msb == 0x0F {
+            // Exponent-multiplier notation: 2^E * (MM*2+1)
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = ((lsb & 0x03) * 2 + 1) as usize;
+            // Sizes that don't fit are clamped, the length check rejects them later.
+            2usize.checked_pow(exponent).map_or(usize::MAX, |size| size.saturating_mul(multiplier))
+        } else {
+            ((msb as usize) << 8 | lsb as usize) * unit
+        }
+    }
+
+    fn ram_size(shift: u8) -> usize {
+        if shift == 0 { 0 } else { 64 << shift }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16 byte header from bytes 4-15, followed by `len` bytes of data.
+    fn rom(fields: [u8; 12], len: usize) -> Vec<u8> {
+        let mut bytes = b"NES\x1A".to_vec();
+        bytes.extend_from_slice(&fields);
+        bytes.resize(HEADER_SIZE + len, 0);
+        bytes
+    }
+
+    #[test]
+    fn ines() {
+        // (bytes 4-15, mapper, mirroring, prg ram, prg nvram, chr ram, console, timing)
+        let cases = [
+            ([2, 1, 0x11, 0x00, 0, 0, 0, 0, 0, 0, 0, 0], 1, Mirroring::Vertical, 0x2000, 0, 0, Console::Nes, Timing::Ntsc),
+            ([2, 0, 0x12, 0x40, 0, 0, 0, 0, 0, 0, 0, 0], 0x41, Mirroring::Horizontal, 0, 0x2000, 0x2000, Console::Nes, Timing::Ntsc),
+            ([2, 1, 0x08, 0x01, 4, 1, 0, 0, 0, 0, 0, 0], 0, Mirroring::FourScreen, 0x8000, 0, 0, Console::VsSystem { ppu: 0, hardware: 0 }, Timing::Pal),
+            ([2, 1, 0x00, 0x02, 0, 0, 0, 0, 0, 0, 0, 0], 0, Mirroring::Horizontal, 0x2000, 0, 0, Console::Playchoice, Timing::Ntsc),
+        ];
+        for (fields, mapper, mirroring, prg_ram, prg_nvram, chr_ram, console, timing) in cases {
+            let header = Header::new(&rom(fields, fields[0] as usize * 0x4000 + fields[1] as usize * 0x2000)).unwrap();
+            assert!(!header.nes2);
+            assert_eq!(header.mapper, mapper);
+            assert_eq!(header.submapper, 0);
+            assert_eq!(header.mirroring, mirroring);
+            assert_eq!(header.battery, prg_nvram != 0);
+            assert_eq!((header.prg_ram_size, header.prg_nvram_size, header.chr_ram_size), (prg_ram, prg_nvram, chr_ram));
+            assert_eq!(header.console, console);
+            assert_eq!(header.timing, timing);
+            assert_eq!(header.chr_offset, HEADER_SIZE + fields[0] as usize * 0x4000);
+        }
+    }
+
+    #[test]
+    fn disk_dude() {
+        // "DiskDude!" over bytes 7-15: only the low mapper nibble is real.
+        let mut fields = [1, 1, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        fields[3..12].copy_from_slice(b"DiskDude!");
+        let header = Header::new(&rom(fields, 0x6000)).unwrap();
+        assert!(!header.nes2);
+        assert_eq!(header.mapper, 4);
+        assert_eq!(header.console, Console::Nes);
+        assert_eq!(header.timing, Timing::Ntsc);
+    }
+
+    #[test]
+    fn nes2() {
+        // Mapper 0x141 submapper 2, 8KB PRG RAM + 32KB PRG NVRAM, 8KB CHR RAM, PAL, VS. System.
+        let fields = [2, 0, 0x13, 0x49, 0x21, 0x00, 0x97, 0x07, 0x01, 0x13, 0x02, 0x05];
+        let header = Header::new(&rom(fields, 0x8000)).unwrap();
+        assert!(header.nes2);
+        assert_eq!(header.mapper, 0x141);
+        assert_eq!(header.submapper, 2);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.battery);
+        assert_eq!((header.prg_rom_size, header.chr_rom_size), (0x8000, 0));
+        assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0x2000, 0x8000));
+        assert_eq!((header.chr_ram_size, header.chr_nvram_size), (0x2000, 0));
+        assert_eq!(header.timing, Timing::Pal);
+        assert_eq!(header.console, Console::VsSystem { ppu: 3, hardware: 1 });
+        assert_eq!(header.misc_roms, 2);
+        assert_eq!(header.expansion, 5);
+    }
+
+    #[test]
+    fn nes2_exponent_size() {
+        // PRG: 2^14 * 3 = 48KB, CHR: 2^13 * 1 = 8KB.
+        let fields = [(14 << 2) | 1, 13 << 2, 0, 0x08, 0, 0xFF, 0, 0, 0, 0, 0, 0];
+        let header = Header::new(&rom(fields, 0xE000)).unwrap();
+        assert_eq!((header.prg_rom_size, header.chr_rom_size), (0xC000, 0x2000));
+        // (lsb, msb, unit, size)
+        let cases = [
+            (0x02, 0x00, 0x4000, 0x8000),
+            (0x00, 0x01, 0x4000, 0x400000),
+            (0x00, 0x0F, 0x4000, 1),
+            (0x03, 0x0F, 0x4000, 7),
+            (0x07, 0x0F, 0x4000, 14),
+            (0x2A, 0x0F, 0x2000, 0x1400),
+            (0xFF, 0x0F, 0x4000, usize::MAX),
+        ];
+        for (lsb, msb, unit, size) in cases {
+            assert_eq!(Header::rom_size(lsb, msb, unit), size, "{lsb:02X} {msb:02X}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Header::new(b"NES"), Err(LoadError::UnknownFormat)));
+        assert!(matches!(Header::new(b"NES\x1A\x01"), Err(LoadError::TruncatedHeader)));
+        assert!(matches!(Header::new(&rom([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x4000)),
+            Err(LoadError::SizeMismatch { expected: 0x6010, actual: 0x4010 })));
+        assert!(matches!(Header::new(&rom([0, 0, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x100)), Err(LoadError::BadTrainer)));
+        let header = Header::new(&rom([1, 0, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x4200)).unwrap();
+        assert_eq!(header.prg_offset, HEADER_SIZE + TRAINER_SIZE);
+    }
+}
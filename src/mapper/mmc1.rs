@@ -1,47 +1,36 @@
-use super::Mapper;
+use super::*;
 use std::fmt;
-use crate::mapper::Mirroring;
 
-const PRG_BANK_SIZE_256: usize = 0x40000;
 const PRG_BANK_SIZE_32: usize = 0x8000;
 const PRG_BANK_SIZE_16: usize = 0x4000;
 const PRG_BANK_SIZE_8: usize = 0x2000; // PRG RAM bank size
 const CHR_BANK_SIZE_8: usize = 0x2000;
 const CHR_BANK_SIZE_4: usize = 0x1000;
 
-use ChrBanks::*;
-use BankType::*;
-
-#[derive(Clone, Copy, Debug)]
-enum BankType {
-    Fixed,
-    Switch(usize),
-    Null
-}
-
-type PrgBanks = (BankType, BankType);
-
-#[derive(Clone, Copy, Debug)]
-enum ChrBanks {
-    Ram(usize, Option<usize>),
-    Rom(usize, Option<usize>) 
+// Boards that wire the unused CHR bank lines to something else.
+// https://www.nesdev.org/wiki/MMC1#SxROM_connection_variants
+#[allow(clippy::upper_case_acronyms)]
+#[derive(PartialEq, Clone, Copy)]
+enum Board {
+    Standard,
+    SNROM, // CHR bit 4 disables PRG RAM
+    SOROM, // CHR bit 3 selects an 8KB PRG RAM bank (16KB)
+    SUROM, // CHR bit 4 selects a 256KB PRG ROM bank (512KB)
+    SXROM, // SUROM + CHR bits 2-3 select an 8KB PRG RAM bank (32KB)
+    SEROM, // 32KB PRG ROM, not banked
 }
 
-// MMC1 with 512K(PRG-ROM) was supported by re-using a line from the CHR banking controls. 
 // https://www.nesdev.org/wiki/MMC1
 pub struct MMC1 {
+    board: Board,
+    cycle: usize,
+    last_write: Option<usize>,
     sr: u8,
-    is_variant: bool,
-    chr_addr: ChrBanks,
-    prg_rom_addr: PrgBanks,
-    prg_ram_addr: usize,
-    prg_area: usize,
-    prg_ram: [u8; 0x8000],
-    chr_ram: [u8; 0x20000], // 128KB (2**10 * 128)
-    prg_offset: usize, 
-    prg_rom_len: usize, 
-    chr_offset: usize, 
-    chr_rom_len: usize, 
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+    cartridge: Cartridge,
     mirroring: Mirroring
 }
 
@@ -52,56 +41,82 @@ impl fmt::Display for MMC1 {
 }
 
 impl MMC1 {
-    pub fn new(prg_rom_len: usize, chr_rom_len: usize, prg_offset: usize, chr_offset: usize, mirroring: Mirroring) -> Self { 
-        // (No CHR_ROM) or (CHR_ROM == 8) => Using 8KB variant
-        let is_rom = chr_rom_len != 0;
-        let chr_addr = if is_rom { Rom(0, None) } else { Ram(0, None) };
-        MMC1 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut mmc1 = MMC1 {
+            board: MMC1::get_board(header),
+            cycle: 0,
+            last_write: None,
             sr: 0x10,
-            // is_variant: !is_rom || chr_rom.len() == 0x8000,
-            is_variant: false,
-            chr_addr,
-            prg_rom_addr: (Switch(0), Fixed),
-            prg_ram_addr: 0,
-            prg_area: 0,
-            mirroring,
-            prg_ram: [0; 0x8000],
-            chr_ram: [0; 0x20000], 
-            prg_offset,
-            prg_rom_len, 
-            chr_offset,
-            chr_rom_len, 
-        } 
+            // Power-on state varies between chips, but PRG mode 3 (last bank fixed) is what games can rely on.
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        mmc1.update_banks();
+        mmc1
     }
 
-    fn set_variant(&mut self, value: u8, ignore: bool, register: usize) {
-        let bank = ((value & 1) as usize) * 0x1000 ;
+    fn get_board(header: &Header) -> Board {
+        let prg_ram_size = header.prg_ram_size + header.prg_nvram_size;
+        if header.submapper == 5 { return Board::SEROM }
+        if prg_ram_size >= 0x8000 { return Board::SXROM }
+        if header.prg_rom_size > 0x40000 { return Board::SUROM }
+        if prg_ram_size == 0x4000 { return Board::SOROM }
+        if header.chr_rom_size == 0 { return Board::SNROM }
+        Board::Standard
+    }
 
-        if register == 1 && !ignore {
-            match self.chr_addr {
-                Ram(_, b) => self.chr_addr = Ram(bank * CHR_BANK_SIZE_4, b),
-                Rom(_, b) => self.chr_addr = Rom(bank * CHR_BANK_SIZE_4, b),
-            } 
-        }
-        if register == 2 {
-            match self.chr_addr {
-                Ram(a, Some(_)) => self.chr_addr = Ram(a, Some(bank * CHR_BANK_SIZE_4)),
-                Rom(a, Some(_)) => self.chr_addr = Rom(a, Some(bank * CHR_BANK_SIZE_4)),
-                _ => (),
-            } 
+    fn update_banks(&mut self) {
+        let chr = self.cartridge.chr_memory();
+        if self.control & 0x10 == 0 { // 8KB CHR mode
+            self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_8, chr, (self.chr_bank_0 >> 1) as usize);
+        } else {
+            self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_4, chr, self.chr_bank_0 as usize);
+            self.cartridge.map_chr(0x1000, CHR_BANK_SIZE_4, chr, self.chr_bank_1 as usize);
         }
 
-        let prg_bank = ((value & 0x0C) >> 2) as usize;
-        self.prg_ram_addr = prg_bank * PRG_BANK_SIZE_8;
+        // The extra lines follow CHR bank 0, games keep both registers in sync when it matters.
+        let chr_bank = self.chr_bank_0 as usize;
+        let outer = match self.board {
+            Board::SUROM | Board::SXROM => chr_bank & 0x10,
+            _ => 0
+        };
+        let bank = (self.prg_bank & 0x0F) as usize | outer;
+        match (self.control & 0x0C) >> 2 {
+            _ if self.board == Board::SEROM => self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_32, Memory::Rom, 0),
+            0 | 1 => self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_32, Memory::Rom, bank >> 1),
+            2 => {
+                self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_16, Memory::Rom, outer);
+                self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_16, Memory::Rom, bank);
+            },
+            _ => {
+                let last = (self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_16) - 1).min(0x0F) | outer;
+                self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_16, Memory::Rom, bank);
+                self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_16, Memory::Rom, last);
+            }
+        }
 
-        // The 256 KB PRG bank selection applies to all the PRG area(0x6000 - 0xFFFF), including the supposedly "fixed" bank.
-        let prg_bank = ((value & 0x10) >> 4) as usize;
-        self.prg_area = prg_bank * PRG_BANK_SIZE_256;
+        let ram_bank = match self.board {
+            Board::SOROM => (chr_bank >> 3) & 0x01,
+            Board::SXROM => (chr_bank >> 2) & 0x03,
+            _ => 0
+        };
+        // MMC1B: PRG bank bit 4 disables PRG RAM.
+        let ram_disabled = self.prg_bank & 0x10 != 0 || (self.board == Board::SNROM && chr_bank & 0x10 != 0);
+        if ram_disabled {
+            self.cartridge.unmap_prg(0x6000, PRG_BANK_SIZE_8);
+        } else {
+            self.cartridge.map_prg(0x6000, PRG_BANK_SIZE_8, Memory::Ram, ram_bank);
+        }
     }
 
     fn set_reg(&mut self, reg: u16, value: u8) {
         match reg {
             0 => { // Control register
+                self.control = value;
                 match value & 0x03 {
                     0 => self.mirroring = Mirroring::OneScreenLower,
                     1 => self.mirroring = Mirroring::OneScreenUpper,
@@ -109,154 +124,202 @@ impl MMC1 {
                     3 => self.mirroring = Mirroring::Horizontal,
                     _ => ()
                 };
-                match (value & 0x0C) >> 2 {
-                    0 | 1 => self.prg_rom_addr = (Switch(0), Null),
-                    2 => self.prg_rom_addr = (Fixed, Switch(0)),
-                    3 => self.prg_rom_addr = (Switch(0), Fixed),
-                    _ => ()
-                }
-                match (value & 0x10) >> 4 {
-                    0 => self.chr_addr = {
-                        if self.chr_rom_len != 0 {
-                            Rom(0, None)
-                        } else {
-                            Ram(0, None)
-                        }
-                    },
-                    1 => self.chr_addr = {
-                        if self.chr_rom_len != 0 {
-                            Rom(0, Some(0x1000))
-                        } else {
-                            Ram(0, Some(0x1000))
-                        }
-                    },
-                    _ => ()
-                }
-            },
-            1 => { // CHR bank 0 register
-                if !self.is_variant {
-                    match self.chr_addr {
-                        Rom(_, None) => {
-                            let bank = (value & 0x1E) as usize;
-                            self.chr_addr = Rom(bank * CHR_BANK_SIZE_8, None);
-                        },
-                        Ram(_, None) => {
-                            let bank = (value & 0x1E) as usize;
-                            self.chr_addr = Ram(bank * CHR_BANK_SIZE_8, None);
-                        },
-                        Rom(_, b) => {
-                            let bank  = (value & 0x1F) as usize;
-                            self.chr_addr = Rom(bank * CHR_BANK_SIZE_4, b);
-                        },
-                        Ram(_, b) => {
-                            let bank  = (value & 0x1F) as usize;
-                            self.chr_addr = Ram(bank * CHR_BANK_SIZE_4, b);
-                        },
-                    }
-                } else { // High Address lines are used for different purposes
-                    match self.chr_addr {
-                        Ram(_, None) | Rom(_, None) => self.set_variant(value, true, 1),
-                        _ => self.set_variant(value, false, 1),
-                    }
-                }
-            },
-            2 => { // CHR bank 1 register
-                if !self.is_variant {
-                    match self.chr_addr {
-                        Rom(a, Some(_)) => {
-                            let bank  = (value & 0x1F) as usize;
-                            self.chr_addr = Rom(a, Some(bank * CHR_BANK_SIZE_4));
-                        },
-                        Ram(a, Some(_)) => {
-                            let bank  = (value & 0x1F) as usize;
-                            self.chr_addr = Ram(a, Some(bank * CHR_BANK_SIZE_4));
-                        },
-                        _ => ()
-                    }
-                } else {
-                    match self.chr_addr {
-                        Ram(_, Some(_)) | Rom(_, Some(_)) => self.set_variant(value, false, 2),
-                        _ => (),
-                    }
-                }
-            }, 
-            3 => { // PRG bank register
-                match self.prg_rom_addr {
-                    (_, Null) => {
-                        let bank = (value & 0x0E) as usize;
-                        self.prg_rom_addr = (Switch(bank * PRG_BANK_SIZE_32), Null);
-                    },
-                    (a, Switch(_)) => {
-                        let bank  = (value & 0x0F) as usize;
-                        self.prg_rom_addr = (a, Switch(bank * PRG_BANK_SIZE_16));
-                    },
-                    (Switch(_), b) => {
-                        let bank  = (value & 0x0F) as usize;
-                        self.prg_rom_addr = (Switch(bank * PRG_BANK_SIZE_16), b);
-                    }
-                    _ => ()
-                }
             },
+            1 => self.chr_bank_0 = value, // CHR bank 0 register
+            2 => self.chr_bank_1 = value, // CHR bank 1 register
+            3 => self.prg_bank = value, // PRG bank register
             _ => { panic!("MMC1: Unknown register."); }
         };
+        self.update_banks();
     }
 
     fn update_sr(&mut self, value: u8, addr: u16) {
-        if (value & 0x7) != 0 { self.sr = 0x10; return; }
-        if self.sr & 1 == 1 {
-            self.sr >>= 1;
-            self.sr |= (value & 0x1) << 4;
+        // Writes on consecutive cycles (the two writes of INC, DEC, etc.) only see the first one.
+        let consecutive = self.last_write.is_some_and(|cycle| self.cycle.wrapping_sub(cycle) < 2);
+        self.last_write = Some(self.cycle);
+        if consecutive { return }
+
+        if value & 0x80 != 0 {
+            self.sr = 0x10;
+            self.control |= 0x0C;
+            self.update_banks();
+            return
+        }
+        // The 1 shifted in at bit 4 reaches bit 0 after four writes, the fifth one loads the register.
+        let full = self.sr & 1 == 1;
+        self.sr = (self.sr >> 1) | (value & 0x1) << 4;
+        if full {
             let reg = ((addr & 0xF000) >> 13) - 4;
             self.set_reg(reg, self.sr);
             self.sr = 0x10;
-        } else {
-            self.sr >>= 1;
-            self.sr |= (value & 0x1) << 5;
         }
     }
 }
 
 impl Mapper for MMC1 {
-    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
 
-    fn read_prg(&self, rom: *const u8, addr: u16) -> u8 { 
-        if addr < 0x6000 { return 0 }
-        if (0x6000..=0x7FFF).contains(&addr) { return self.prg_ram[(addr -  0x6000) as usize + self.prg_ram_addr + self.prg_area] }
-        let mut addr = addr as usize - 0x8000;
-        if self.prg_rom_len == 0x4000 && addr >= 0x4000 { return unsafe { *(rom.wrapping_add(self.prg_offset + addr % 0x4000)) } }
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
 
-        match self.prg_rom_addr {
-            (_, Switch(x)) if addr >= 0x4000 => addr = addr - PRG_BANK_SIZE_16 + x + self.prg_area,
-            (_, Fixed) if addr >= 0x4000 => addr += self.prg_rom_len - 2*PRG_BANK_SIZE_16 + self.prg_area,
-            (Switch(x), _) => addr += x + self.prg_area,
-            (Fixed,     _) => addr += self.prg_area,
-            _  => panic!("MMC1: (Null, Null)")
-        }
-        unsafe { *(rom.wrapping_add(self.prg_offset + addr)) }
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        vec![
+            ("shift".to_string(), self.sr as u32),
+            ("control".to_string(), self.control as u32),
+            ("chr_0".to_string(), self.chr_bank_0 as u32),
+            ("chr_1".to_string(), self.chr_bank_1 as u32),
+            ("prg".to_string(), self.prg_bank as u32),
+        ]
     }
 
-    fn write_prg(&mut self, addr: u16, val: u8) { 
+    fn write_prg(&mut self, addr: u16, val: u8) {
         match addr {
-            0x6000..=0x7FFF => self.prg_ram[(addr -  0x6000) as usize + self.prg_ram_addr + self.prg_area] = val,
+            0x6000..=0x7FFF => self.cartridge.write_prg(addr, val),
             0x8000..=0xFFFF => self.update_sr(val, addr),
             _ => ()
         }
     }
 
-    fn read_chr(&self, rom: *const u8, addr: u16) -> u8 {
-        match self.chr_addr {
-            Ram(_, Some(x)) if addr >= 0x1000 => self.chr_ram[addr as usize + x - CHR_BANK_SIZE_4],
-            Rom(_, Some(x)) if addr >= 0x1000 => unsafe { *(rom.wrapping_add(self.chr_offset + addr as usize + x - CHR_BANK_SIZE_4)) },
-            Ram(x, _) => self.chr_ram[addr as usize + x],
-            Rom(x, _) => unsafe { *(rom.wrapping_add(self.chr_offset + addr as usize + x)) },
+    fn tick(&mut self) {
+        self.cycle = self.cycle.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn mmc1(header: &Header) -> MMC1 {
+        MMC1::new(header, cartridge(header))
+    }
+
+    // Five serial writes, far enough apart not to be merged.
+    fn write(mmc1: &mut MMC1, addr: u16, value: u8) {
+        for bit in 0..5 {
+            mmc1.write_prg(addr, value >> bit);
+            mmc1.tick();
+            mmc1.tick();
+        }
+    }
+
+    #[test]
+    fn prg_and_chr_banks() {
+        let mut mmc1 = mmc1(&header(1, 0x20000, 0x20000));
+        // Power on: first bank at $8000, last one fixed at $C000.
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (0, 14));
+        write(&mut mmc1, 0xE000, 3);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xA000), mmc1.read_prg(0xC000)), (6, 7, 14));
+
+        // Mode 2: first bank fixed at $8000.
+        write(&mut mmc1, 0x8000, 0x0B);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (0, 6));
+        assert_eq!(mmc1.get_mirroring(), Mirroring::Horizontal);
+        // Mode 0: 32KB, bit 0 ignored.
+        write(&mut mmc1, 0x8000, 0x02);
+        write(&mut mmc1, 0xE000, 5);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xE000)), (8, 11));
+        assert_eq!(mmc1.get_mirroring(), Mirroring::Vertical);
+
+        // 8KB CHR mode ignores bit 0 and the second register.
+        write(&mut mmc1, 0xA000, 3);
+        write(&mut mmc1, 0xC000, 5);
+        assert_eq!((mmc1.read_chr(0x0000), mmc1.read_chr(0x1C00)), (8, 15));
+        write(&mut mmc1, 0x8000, 0x10);
+        assert_eq!((mmc1.read_chr(0x0000), mmc1.read_chr(0x1000)), (12, 20));
+        assert_eq!(mmc1.get_mirroring(), Mirroring::OneScreenLower);
+    }
+
+    #[test]
+    fn shift_register() {
+        let mut mmc1 = mmc1(&header(1, 0x20000, 0x2000));
+        // The second write of a read-modify-write instruction is ignored.
+        for value in [1, 1, 0, 0, 0] {
+            mmc1.write_prg(0xE000, value);
+            mmc1.tick();
+            mmc1.write_prg(0xE000, value);
+            mmc1.tick();
+            mmc1.tick();
         }
+        assert_eq!(mmc1.read_prg(0x8000), 6);
+
+        // Bit 7 clears the shift register and restores PRG mode 3.
+        write(&mut mmc1, 0x8000, 0x00);
+        mmc1.write_prg(0xE000, 1);
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.write_prg(0x8000, 0x80);
+        mmc1.tick();
+        mmc1.tick();
+        write(&mut mmc1, 0xE000, 2);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (4, 14));
+    }
+
+    #[test]
+    fn prg_ram() {
+        let mut header = header(1, 0x20000, 0x2000);
+        header.prg_ram_size = 0x2000;
+        let mut mmc1 = mmc1(&header);
+        mmc1.write_prg(0x6000, 0x42);
+        assert_eq!(mmc1.read_prg(0x6000), 0x42);
+        // MMC1B: PRG bit 4 disables the RAM.
+        write(&mut mmc1, 0xE000, 0x10);
+        mmc1.get_cartridge_mut().open_bus = 0x60;
+        assert_eq!(mmc1.read_prg(0x6000), 0x60);
+        write(&mut mmc1, 0xE000, 0x00);
+        assert_eq!(mmc1.read_prg(0x6000), 0x42);
     }
 
-    fn write_chr(&mut self, addr: u16, val: u8) { 
-        match self.chr_addr {
-            Ram(_, Some(x)) if addr >= 0x1000 => self.chr_ram[addr as usize + x - CHR_BANK_SIZE_4] = val,
-            Ram(x, _) => self.chr_ram[addr as usize + x] = val,
-            _ => (),
+    #[test]
+    fn snrom() {
+        let mut header = header(1, 0x40000, 0);
+        header.prg_ram_size = 0x2000;
+        header.chr_ram_size = 0x2000;
+        let mut mmc1 = mmc1(&header);
+        assert!(mmc1.board == Board::SNROM);
+        mmc1.write_prg(0x6000, 0x42);
+        write(&mut mmc1, 0xA000, 0x10);
+        mmc1.get_cartridge_mut().open_bus = 0x60;
+        assert_eq!(mmc1.read_prg(0x6000), 0x60);
+        write(&mut mmc1, 0xA000, 0x00);
+        assert_eq!(mmc1.read_prg(0x6000), 0x42);
+    }
+
+    #[test]
+    fn sorom_and_sxrom_ram_banks() {
+        for (prg_ram_size, board, banks) in [(0x4000, Board::SOROM, [0x00, 0x08]), (0x8000, Board::SXROM, [0x04, 0x0C])] {
+            let mut header = header(1, 0x40000, 0);
+            header.prg_ram_size = prg_ram_size;
+            let mut mmc1 = mmc1(&header);
+            assert!(mmc1.board == board);
+            for (n, bank) in banks.into_iter().enumerate() {
+                write(&mut mmc1, 0xA000, bank);
+                mmc1.write_prg(0x6000, n as u8 + 1);
+            }
+            for (n, bank) in banks.into_iter().enumerate() {
+                write(&mut mmc1, 0xA000, bank);
+                assert_eq!(mmc1.read_prg(0x6000), n as u8 + 1);
+            }
         }
     }
+
+    #[test]
+    fn surom_outer_bank() {
+        let mut mmc1 = mmc1(&header(1, 0x80000, 0));
+        assert!(mmc1.board == Board::SUROM);
+        write(&mut mmc1, 0xE000, 2);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (4, 30));
+        // CHR bit 4 selects the second 256KB, the fixed bank follows.
+        write(&mut mmc1, 0xA000, 0x10);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (36, 62));
+    }
+
+    #[test]
+    fn serom() {
+        let mut header = header(1, 0x8000, 0x8000);
+        header.submapper = 5;
+        let mut mmc1 = mmc1(&header);
+        write(&mut mmc1, 0xE000, 1);
+        assert_eq!((mmc1.read_prg(0x8000), mmc1.read_prg(0xC000)), (0, 2));
+    }
 }
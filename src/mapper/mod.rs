@@ -1,16 +1,45 @@
 mod nrom;
 mod cnrom;
 mod mmc1;
+mod namco108;
+mod g101;
+mod h3001;
+mod tc0190;
+mod x1005;
+mod ss88006;
+mod multicart;
+mod vs_unisystem;
+mod debug;
+mod fds;
+mod header;
+mod database;
+mod unif;
+mod cartridge;
+mod error;
 
 pub use crate::mapper::{
     nrom::NROM,
     cnrom::CNROM,
-    mmc1::MMC1
+    mmc1::MMC1,
+    namco108::Namco108,
+    g101::G101,
+    h3001::H3001,
+    tc0190::TC0190,
+    x1005::X1005,
+    ss88006::SS88006,
+    multicart::Multicart,
+    vs_unisystem::VsUnisystem,
+    fds::FDS,
+    header::*,
+    database::Corrections,
+    cartridge::*,
+    debug::*,
+    error::LoadError
 };
 
 use std::fmt::Display;
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Mirroring {
     OneScreenUpper,
     OneScreenLower,
@@ -19,23 +48,15 @@ pub enum Mirroring {
     FourScreen
 }
 
-pub type Mapper_ = Box<dyn Mapper>;
-
-pub trait Mapper: Display {
-    fn read_prg(&self, rom: *const u8, addr: u16) -> u8;
-    fn read_chr(&self, rom: *const u8, addr: u16) -> u8;
-    fn write_prg(&mut self, addr: u16, val: u8);
-    fn write_chr(&mut self, addr: u16, val: u8);
-    fn get_mirroring(&self) -> Mirroring;
-
-    fn mirror(&self, addr: u16) -> u16 {
+impl Mirroring {
+    // VRAM index of a nametable address.
+    pub fn mirror(&self, addr: u16) -> u16 {
         let mirrored_vram = addr & 0x2FFF;
         let vram_index = mirrored_vram - 0x2000;
         let name_table = vram_index / 0x400;
-        let mirroring = self.get_mirroring();
         // 00b - 1-screen mirroring (nametable 0)
         // 01b - 1-screen mirroring (nametable 1)
-        match (mirroring, name_table) {
+        match (*self, name_table) {
             (Mirroring::Vertical, 2) | (Mirroring::Vertical, 3) => vram_index - 0x800,
             (Mirroring::Horizontal, 1) => vram_index - 0x400,
             (Mirroring::Horizontal, 2) => vram_index - 0x400,
@@ -49,45 +70,118 @@ pub trait Mapper: Display {
     }
 }
 
-pub fn get_mapper(prg_len: usize, chr_len: usize, prg_offset: usize, chr_offset: usize, mapper: u8, mirroring: Mirroring) -> Result<Box<dyn Mapper>, String> { 
-    match mapper {
-        0 => Ok(Box::new(NROM::new(prg_len, chr_len, prg_offset, chr_offset, mirroring))),
-        1 => Ok(Box::new(MMC1::new(prg_len, chr_len, prg_offset, chr_offset, mirroring))),
-        3 => Ok(Box::new(CNROM::new(prg_len, chr_len, prg_offset, chr_offset, mirroring))),
-        _ => Err("Mapper not implemented.".to_string())
-    }
-}
+pub type Mapper_ = Box<dyn Mapper>;
+
+pub trait Mapper: Display {
+    fn get_cartridge(&self) -> &Cartridge;
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge;
+    fn write_prg(&mut self, addr: u16, val: u8);
+    fn get_mirroring(&self) -> Mirroring;
+
+    // Writes to the CPU's own registers ($4000-$401F), for boards wired to them.
+    fn write_register(&mut self, _addr: u16, _val: u8) {}
+
+    fn read_prg(&mut self, addr: u16) -> u8 { self.get_cartridge().read_prg(addr) }
+    fn read_chr(&self, addr: u16) -> u8 { self.get_cartridge().read_chr(addr) }
+    fn write_chr(&mut self, addr: u16, val: u8) { self.get_cartridge_mut().write_chr(addr, val) }
 
-pub fn new(bytes: &Vec<u8>) -> Result<Mapper_, String> {
-    if bytes[0] == 0x4E && bytes[1] == 0x45 && bytes[2] == 0x53 && bytes[3] == 0x1A {
-        if bytes[7] & 0x12 == 2 { return Err("NES 2.0 not supported(yet).".to_string()) }
+    // Console reset button (not power on).
+    fn reset(&mut self) {}
 
-        let prg_rom_banks = bytes[4] as usize; // 16384
-        // Size of CHR ROM in 8 KB units (value 0 means the board uses CHR RAM)
-        let chr_rom_banks = bytes[5] as usize; // 8192
+    // Clocked every CPU cycle.
+    fn tick(&mut self) {}
 
-        let four_screen = bytes[6] & 0x8 != 0;
-        let vertical_mirroring = bytes[6] & 0x1 != 0;
-        let mirroring = match (four_screen, vertical_mirroring) {
-            (true, _) => Mirroring::FourScreen,
-            (false, true) => Mirroring::Vertical,
-            (false, false) => Mirroring::Horizontal,
-        };
+    // Clocked once per rendered scanline, where MMC3-style counters see PPU A12 rise.
+    fn scanline(&mut self) {}
 
-        let skip_trainer = bytes[6] & 0x04 != 0;
+    // IRQ line, held until the mapper acknowledges it.
+    fn irq(&self) -> bool { false }
 
-        let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
-        let chr_rom_start = prg_rom_start + prg_rom_banks * 0x4000;
+    // Expansion audio level (0.0 - 1.0).
+    fn get_audio_output(&self) -> f32 { 0.0 }
 
-        let mapper_id = (bytes[7] & 0xF0) | (bytes[6] & 0xF0) >> 4;
+    // Battery backed PRG RAM, kept between sessions.
+    fn has_battery(&self) -> bool { self.get_cartridge().has_battery() }
+    fn get_save_ram(&mut self) -> Option<&mut [u8]> { self.get_cartridge_mut().get_save_ram() }
 
-        let mapper = match get_mapper(prg_rom_banks * 0x4000, chr_rom_banks * 0x2000, prg_rom_start, chr_rom_start, mapper_id, mirroring) {
-            Ok(mapper) => mapper,
-            Err(str) => return Err(str)
-        };
+    fn as_fds(&mut self) -> Option<&mut FDS> { None }
 
-        Ok(mapper)
+    // Mapper registers (bank numbers, IRQ counters, ...) by name, for debuggers.
+    fn get_registers(&self) -> Vec<(String, u32)> { Vec::new() }
+
+    // Bank windows, mirroring and registers.
+    fn get_debug_state(&self) -> DebugState { DebugState::new(self) }
+
+    fn mirror(&self, addr: u16) -> u16 { self.get_mirroring().mirror(addr) }
+}
+
+pub fn get_mapper(header: &Header, cartridge: Cartridge) -> Result<Box<dyn Mapper>, LoadError> {
+    match header.mapper {
+        0 => Ok(Box::new(NROM::new(header, cartridge))),
+        1 => Ok(Box::new(MMC1::new(header, cartridge))),
+        3 => Ok(Box::new(CNROM::new(header, cartridge))),
+        18 => Ok(Box::new(SS88006::new(header, cartridge))),
+        32 => Ok(Box::new(G101::new(header, cartridge))),
+        33 | 48 => Ok(Box::new(TC0190::new(header, cartridge))),
+        65 => Ok(Box::new(H3001::new(header, cartridge))),
+        76 | 88 | 95 | 154 | 206 => Ok(Box::new(Namco108::new(header, cartridge))),
+        80 => Ok(Box::new(X1005::new(header, cartridge))),
+        99 => Ok(Box::new(VsUnisystem::new(header, cartridge))),
+        225 | 226 | 228 | 233 => Ok(Box::new(Multicart::new(header, cartridge))),
+        _ => Err(LoadError::UnsupportedMapper(header.mapper))
+    }
+}
+
+// Also returns the console the game was made for. `database` holds game database entries
+// checked before the built-in ones (see database.rs for the format).
+pub fn new(bytes: &[u8], bios: &[u8], database: &str) -> Result<(Mapper_, Console, Corrections), LoadError> {
+    if FDS::is_disk(bytes) {
+        Ok((Box::new(FDS::new(bytes, bios)?), Console::Nes, Corrections::empty()))
+    } else if unif::is_unif(bytes) {
+        let (header, image) = unif::parse(bytes)?;
+        let cartridge = Cartridge::from_header(&header, &image)?;
+        Ok((get_mapper(&header, cartridge)?, header.console, Corrections::empty()))
     } else {
-        Err("Only NES files supported.".to_string())
+        let mut header = Header::new(bytes)?;
+        // NES 2.0 headers are trusted, iNES 1.0 ones are checked against the database.
+        let corrections = if header.nes2 { Corrections::empty() } else { database::correct(&mut header, bytes, database) };
+        let cartridge = Cartridge::from_header(&header, bytes)?;
+        Ok((get_mapper(&header, cartridge)?, header.console, corrections))
+    }
+}
+
+// Test boards: each PRG ROM byte holds its 8KB bank number and each CHR ROM byte its 1KB bank number,
+// so a read shows which bank is mapped.
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    pub fn header(mapper: u16, prg_rom_size: usize, chr_rom_size: usize) -> Header {
+        Header {
+            nes2: true,
+            mapper,
+            submapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            trainer: false,
+            prg_rom_size,
+            chr_rom_size,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            prg_offset: 0,
+            chr_offset: prg_rom_size,
+            timing: Timing::Ntsc,
+            console: Console::Nes,
+            misc_roms: 0,
+            expansion: 0,
+        }
+    }
+
+    pub fn cartridge(header: &Header) -> Cartridge {
+        let prg = (0..header.prg_rom_size).map(|i| (i / 0x2000) as u8);
+        let chr = (0..header.chr_rom_size).map(|i| (i / 0x400) as u8);
+        Cartridge::from_header(header, &prg.chain(chr).collect::<Vec<u8>>()).unwrap()
     }
 }
@@ -0,0 +1,245 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_32: usize = 0x8000;
+const PRG_BANK_SIZE_16: usize = 0x4000;
+const CHR_BANK_SIZE_8: usize = 0x2000;
+
+// Pirate multicarts (mappers 225, 226, 228 and 233). They all latch an NROM-style
+// game (16 or 32KB PRG, 8KB CHR) and only differ in how the latch is written.
+// https://www.nesdev.org/wiki/INES_Mapper_225
+// https://www.nesdev.org/wiki/INES_Mapper_226
+// https://www.nesdev.org/wiki/INES_Mapper_228
+// https://www.nesdev.org/wiki/INES_Mapper_233
+pub struct Multicart {
+    mapper: u16,
+    prg_bank: usize, // In 16KB units
+    prg_16k: bool,
+    prg_open_bus: bool, // 228: the empty chip socket
+    chr_bank: usize,
+    registers: [u8; 2],
+    outer_bank: usize, // 233: flipped by every reset
+    nibble_ram: [u8; 4],
+    three_screen: bool, // 233: $2000 on page 0, the rest on page 1
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for Multicart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Multicart ({})", self.mapper)
+    }
+}
+
+impl Multicart {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut multicart = Multicart {
+            mapper: header.mapper,
+            prg_bank: 0,
+            prg_16k: false,
+            prg_open_bus: false,
+            chr_bank: 0,
+            registers: [0; 2],
+            outer_bank: 0,
+            nibble_ram: [0; 4],
+            three_screen: false,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        multicart.update_banks();
+        multicart
+    }
+
+    fn update_banks(&mut self) {
+        if self.prg_open_bus {
+            self.cartridge.unmap_prg(0x8000, PRG_BANK_SIZE_32);
+        } else if self.prg_16k {
+            self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_16, Memory::Rom, self.prg_bank);
+            self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_16, Memory::Rom, self.prg_bank);
+        } else {
+            self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_32, Memory::Rom, self.prg_bank >> 1);
+        }
+        let chr = self.cartridge.chr_memory();
+        self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_8, chr, self.chr_bank);
+    }
+
+    fn write_latch(&mut self, addr: u16, val: u8) {
+        let addr = addr as usize;
+        match self.mapper {
+            225 => {
+                // A~[.HMO PPPP PPCC CCCC]
+                let high = (addr >> 14) & 0x01;
+                self.prg_bank = high << 6 | (addr >> 6) & 0x3F;
+                self.chr_bank = high << 6 | addr & 0x3F;
+                self.prg_16k = addr & 0x1000 != 0;
+                self.mirroring = if addr & 0x2000 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+            },
+            226 => {
+                // $8000: [PMOP PPPP], $8001: [.... ...P]
+                self.registers[addr & 0x01] = val;
+                let [low, high] = self.registers.map(|value| value as usize);
+                self.prg_bank = low & 0x1F | (low >> 7) << 5 | (high & 0x01) << 6;
+                self.prg_16k = low & 0x20 != 0;
+                self.mirroring = if low & 0x40 != 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+            },
+            228 => {
+                // A~[..MH HPPP PPO. CCCC], D~[.... ..cc]
+                // Action 52 has three 512KB PRG chips, socket 2 is empty and chip 3 is the third one.
+                let chip = (addr >> 11) & 0x03;
+                self.prg_open_bus = chip == 2;
+                self.prg_bank = chip.min(2) << 5 | (addr >> 6) & 0x1F;
+                self.chr_bank = (addr & 0x0F) << 2 | val as usize & 0x03;
+                self.prg_16k = addr & 0x20 != 0;
+                self.mirroring = if addr & 0x2000 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+            },
+            _ => {
+                // 233: D~[MMOP PPPP]
+                let val = val as usize;
+                self.prg_bank = self.outer_bank << 5 | val & 0x1F;
+                self.prg_16k = val & 0x20 != 0;
+                self.three_screen = val >> 6 == 0;
+                self.mirroring = match val >> 6 {
+                    1 => Mirroring::Vertical,
+                    2 => Mirroring::Horizontal,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            }
+        }
+        self.update_banks();
+    }
+}
+
+impl Mapper for Multicart {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        vec![
+            ("prg".to_string(), self.prg_bank as u32),
+            ("prg_16k".to_string(), self.prg_16k as u32),
+            ("chr".to_string(), self.chr_bank as u32),
+            ("outer".to_string(), self.outer_bank as u32),
+        ]
+    }
+
+    fn read_prg(&mut self, addr: u16) -> u8 {
+        match (self.mapper, addr) {
+            (225, 0x5800..=0x5FFF) | (228, 0x4020..=0x5FFF) => self.nibble_ram[addr as usize & 0x03] & 0x0F,
+            _ => self.cartridge.read_prg(addr)
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match (self.mapper, addr) {
+            (225, 0x5800..=0x5FFF) | (228, 0x4020..=0x5FFF) => self.nibble_ram[addr as usize & 0x03] = val & 0x0F,
+            (_, 0x8000..=0xFFFF) => self.write_latch(addr, val),
+            _ => ()
+        }
+    }
+
+    // The latch is cleared (back to the menu), 233 also moves on to the next 512KB.
+    fn reset(&mut self) {
+        if self.mapper == 233 { self.outer_bank ^= 1; }
+        self.registers = [0; 2];
+        self.write_latch(0x8000, 0);
+    }
+
+    fn mirror(&self, addr: u16) -> u16 {
+        if !self.three_screen { return self.mirroring.mirror(addr) }
+        let vram_index = (addr & 0x2FFF) - 0x2000;
+        if vram_index < 0x400 { vram_index } else { 0x400 | vram_index & 0x3FF }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn multicart(mapper: u16) -> Multicart {
+        let header = header(mapper, 0x200000, 0x40000);
+        Multicart::new(&header, cartridge(&header))
+    }
+
+    fn prg(multicart: &mut Multicart) -> [u8; 4] {
+        [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| multicart.read_prg(addr))
+    }
+
+    #[test]
+    fn mapper_225() {
+        let mut multicart = multicart(225);
+        multicart.write_prg(0x8000 | 0x2000 | 0x1000 | 5 << 6 | 3, 0);
+        assert_eq!((prg(&mut multicart), multicart.read_chr(0)), ([10, 11, 10, 11], 24));
+        assert_eq!(multicart.get_mirroring(), Mirroring::Horizontal);
+        // 32KB mode ignores the low bit, A14 selects the second 1MB.
+        multicart.write_prg(0xC000 | 5 << 6, 0);
+        assert_eq!(prg(&mut multicart), [136, 137, 138, 139]);
+        assert_eq!(multicart.get_mirroring(), Mirroring::Vertical);
+
+        multicart.write_prg(0x5800, 0xAB);
+        multicart.write_prg(0x5FFF, 0xCD);
+        assert_eq!((multicart.read_prg(0x5800), multicart.read_prg(0x5803)), (0x0B, 0x0D));
+
+        multicart.reset();
+        assert_eq!(prg(&mut multicart), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn mapper_226() {
+        let mut multicart = multicart(226);
+        multicart.write_prg(0x8000, 0x80 | 0x40 | 0x20 | 0x05);
+        assert_eq!(prg(&mut multicart), [74, 75, 74, 75]);
+        assert_eq!(multicart.get_mirroring(), Mirroring::Vertical);
+        multicart.write_prg(0x8001, 0x01);
+        assert_eq!(prg(&mut multicart), [202, 203, 202, 203]);
+        multicart.write_prg(0x8000, 0x05);
+        assert_eq!(prg(&mut multicart), [136, 137, 138, 139]);
+        assert_eq!(multicart.get_mirroring(), Mirroring::Horizontal);
+
+        multicart.reset();
+        assert_eq!(prg(&mut multicart), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn mapper_228() {
+        let mut multicart = multicart(228);
+        // Chip 3 is the third 512KB chip: 16KB bank 64 + 4.
+        multicart.write_prg(0x8000 | 0x2000 | 3 << 11 | 4 << 6 | 0x20 | 2, 1);
+        assert_eq!((prg(&mut multicart), multicart.read_chr(0x1C00)), ([136, 137, 136, 137], 79));
+        assert_eq!(multicart.get_mirroring(), Mirroring::Horizontal);
+        multicart.write_prg(0x8000 | 1 << 11 | 4 << 6, 0);
+        assert_eq!((prg(&mut multicart), multicart.read_chr(0)), ([72, 73, 74, 75], 0));
+        // Chip 2 isn't there.
+        multicart.cartridge.open_bus = 0x5A;
+        multicart.write_prg(0x8000 | 2 << 11 | 4 << 6, 0);
+        assert_eq!(prg(&mut multicart), [0x5A; 4]);
+        multicart.write_prg(0x8000 | 2 << 11 | 4 << 6 | 0x20, 0);
+        assert_eq!(prg(&mut multicart), [0x5A; 4]);
+        multicart.reset();
+        assert_eq!(prg(&mut multicart), [0, 1, 2, 3]);
+
+        multicart.write_prg(0x4020, 0x1F);
+        assert_eq!(multicart.read_prg(0x5FFC), 0x0F);
+    }
+
+    #[test]
+    fn mapper_233() {
+        let mut multicart = multicart(233);
+        multicart.write_prg(0x8000, 0x43);
+        assert_eq!(prg(&mut multicart), [4, 5, 6, 7]);
+        assert_eq!(multicart.get_mirroring(), Mirroring::Vertical);
+        multicart.write_prg(0x8000, 0xA3);
+        assert_eq!(prg(&mut multicart), [6, 7, 6, 7]);
+        assert_eq!(multicart.get_mirroring(), Mirroring::Horizontal);
+
+        // Every reset goes back to the menu of the other 512KB.
+        multicart.reset();
+        assert_eq!(prg(&mut multicart), [64, 65, 66, 67]);
+        // Three-screen: $2000 on page 0, the rest on page 1.
+        assert_eq!([0x2000, 0x2400, 0x2800, 0x2C10].map(|addr| multicart.mirror(addr)), [0x000, 0x400, 0x400, 0x410]);
+        multicart.reset();
+        assert_eq!(prg(&mut multicart), [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,194 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_2: usize = 0x800;
+const CHR_BANK_SIZE_1: usize = 0x400;
+
+// Boards built around the chip, they differ in how the CHR lines are wired.
+// https://www.nesdev.org/wiki/Namco_108_family
+#[derive(PartialEq, Clone, Copy)]
+enum Board {
+    DxROM,      // 206: the plain chip
+    Namcot3446, // 76: R2-R5 select 2KB CHR banks, R0-R1 unused
+    Namcot3433, // 88: R0-R1 in the first 64KB of CHR, R2-R5 in the second
+    Namcot3453, // 154: 88 + D6 of any write selects the one-screen nametable
+    Namcot3425, // 95: R0-R1 D5 select the nametable of each half
+}
+
+// Namco 108: MMC3's predecessor, 8 bank registers and no IRQ or mirroring control.
+// https://www.nesdev.org/wiki/INES_Mapper_206
+pub struct Namco108 {
+    board: Board,
+    select: usize,
+    registers: [u8; 8],
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for Namco108 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Namco 108")
+    }
+}
+
+impl Namco108 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let board = match header.mapper {
+            76 => Board::Namcot3446,
+            88 => Board::Namcot3433,
+            154 => Board::Namcot3453,
+            95 => Board::Namcot3425,
+            _ => Board::DxROM,
+        };
+        let mut namco108 = Namco108 {
+            board,
+            select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            cartridge,
+            mirroring: if board == Board::Namcot3453 { Mirroring::OneScreenLower } else { header.mirroring },
+        };
+        namco108.update_banks();
+        namco108
+    }
+
+    fn update_banks(&mut self) {
+        let chr = self.cartridge.chr_memory();
+        let r = self.registers.map(|value| value as usize);
+        if self.board == Board::Namcot3446 {
+            for n in 0..4 {
+                self.cartridge.map_chr(n as u16 * 0x800, CHR_BANK_SIZE_2, chr, r[2 + n]);
+            }
+        } else {
+            // Bank numbers in 1KB units, 2KB banks ignore the low bit.
+            let (mask, upper) = match self.board {
+                Board::Namcot3425 => (0x1F, 0x00),
+                Board::Namcot3433 | Board::Namcot3453 => (0x3F, 0x40),
+                _ => (0x3F, 0x00),
+            };
+            self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_2, chr, (r[0] & mask) >> 1);
+            self.cartridge.map_chr(0x0800, CHR_BANK_SIZE_2, chr, (r[1] & mask) >> 1);
+            for n in 0..4 {
+                self.cartridge.map_chr(0x1000 + n as u16 * 0x400, CHR_BANK_SIZE_1, chr, (r[2 + n] & mask) | upper);
+            }
+        }
+
+        // $C000-$FFFF is fixed to the last 16KB.
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, r[6] & 0x0F);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, r[7] & 0x0F);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, last.saturating_sub(1));
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+    }
+}
+
+impl Mapper for Namco108 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers = vec![("select".to_string(), self.select as u32)];
+        registers.extend(self.registers.iter().enumerate().map(|(n, &value)| (format!("r{n}"), value as u32)));
+        registers
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        if addr < 0x8000 { return }
+        if self.board == Board::Namcot3453 {
+            self.mirroring = if val & 0x40 != 0 { Mirroring::OneScreenUpper } else { Mirroring::OneScreenLower };
+        }
+        match addr & 0xE001 {
+            0x8000 => self.select = (val & 0x07) as usize,
+            0x8001 => {
+                self.registers[self.select] = val & 0x3F;
+                self.update_banks();
+            },
+            _ => ()
+        }
+    }
+
+    fn mirror(&self, addr: u16) -> u16 {
+        if self.board != Board::Namcot3425 { return self.mirroring.mirror(addr) }
+        let vram_index = (addr & 0x2FFF) - 0x2000;
+        let page = (self.registers[vram_index as usize / 0x800] >> 5) & 0x01;
+        (page as u16 * 0x400) | (vram_index & 0x3FF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn namco108(mapper: u16, chr_rom_size: usize) -> Namco108 {
+        let header = header(mapper, 0x20000, chr_rom_size);
+        Namco108::new(&header, cartridge(&header))
+    }
+
+    fn write(namco108: &mut Namco108, register: u8, value: u8) {
+        namco108.write_prg(0x8000, register);
+        namco108.write_prg(0x8001, value);
+    }
+
+    #[test]
+    fn dxrom() {
+        let mut namco108 = namco108(206, 0x10000);
+        assert_eq!((namco108.read_prg(0xC000), namco108.read_prg(0xE000)), (14, 15));
+        write(&mut namco108, 6, 5);
+        write(&mut namco108, 7, 0x19);
+        assert_eq!((namco108.read_prg(0x8000), namco108.read_prg(0xA000), namco108.read_prg(0xC000)), (5, 9, 14));
+
+        // R0-R1 are 2KB banks at $0000, R2-R5 1KB banks at $1000.
+        write(&mut namco108, 0, 7);
+        write(&mut namco108, 1, 10);
+        for (register, value) in [(2, 33), (3, 34), (4, 35), (5, 63)] { write(&mut namco108, register, value); }
+        let chr: Vec<u8> = (0..8).map(|n| namco108.read_chr(n * 0x400)).collect();
+        assert_eq!(chr, [6, 7, 10, 11, 33, 34, 35, 63]);
+        // No mirroring control.
+        namco108.write_prg(0xA000, 1);
+        assert_eq!(namco108.get_mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn namcot3446() {
+        // 76: R2-R5 are 2KB banks.
+        let mut namco108 = namco108(76, 0x20000);
+        write(&mut namco108, 0, 9);
+        for (register, value) in [(2, 1), (3, 2), (4, 3), (5, 40)] { write(&mut namco108, register, value); }
+        let chr: Vec<u8> = (0..4).map(|n| namco108.read_chr(n * 0x800)).collect();
+        assert_eq!(chr, [2, 4, 6, 80]);
+    }
+
+    #[test]
+    fn namcot3433_and_3453() {
+        // 88 and 154: R0-R1 in the first 64KB of CHR, R2-R5 in the second.
+        for mapper in [88, 154] {
+            let mut namco108 = namco108(mapper, 0x20000);
+            write(&mut namco108, 0, 0x42);
+            write(&mut namco108, 2, 0x01);
+            assert_eq!((namco108.read_chr(0x0000), namco108.read_chr(0x1000)), (2, 65));
+        }
+
+        // 154: D6 of any write selects the nametable.
+        let mut namco108 = namco108(154, 0x20000);
+        assert_eq!(namco108.get_mirroring(), Mirroring::OneScreenLower);
+        namco108.write_prg(0xC000, 0x40);
+        assert_eq!(namco108.get_mirroring(), Mirroring::OneScreenUpper);
+        write(&mut namco108, 6, 0x02);
+        assert_eq!(namco108.get_mirroring(), Mirroring::OneScreenLower);
+    }
+
+    #[test]
+    fn namcot3425() {
+        // 95: D5 of R0 and R1 selects the nametable of each half.
+        let mut namco108 = namco108(95, 0x8000);
+        write(&mut namco108, 0, 0x20);
+        write(&mut namco108, 1, 0x00);
+        assert_eq!((namco108.mirror(0x2000), namco108.mirror(0x2400), namco108.mirror(0x2C10)), (0x400, 0x400, 0x010));
+        assert_eq!(namco108.read_chr(0x0000), 0);
+        write(&mut namco108, 1, 0x24);
+        assert_eq!((namco108.mirror(0x2800), namco108.read_chr(0x0800)), (0x400, 4));
+    }
+}
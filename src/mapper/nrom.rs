@@ -1,26 +1,23 @@
 use std::fmt;
 use super::*;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct NROM {
-    prg_ram: [u8; 0x2000],
-    chr_ram: [u8; 0x2000],
-    prg_offset: usize,
-    prg_len: usize,
-    chr_offset: usize,
-    chr_len: usize,
+    cartridge: Cartridge,
     mirroring: Mirroring,
 }
 
 impl NROM {
-    pub fn new(prg_len: usize, chr_len: usize, prg_offset: usize, chr_offset: usize,  mirroring: Mirroring) -> Self { 
+    pub fn new(header: &Header, mut cartridge: Cartridge) -> Self { 
+        // 16KB boards mirror the only bank at $C000.
+        cartridge.map_prg(0x6000, 0x2000, Memory::Ram, 0);
+        cartridge.map_prg(0x8000, 0x4000, Memory::Rom, 0);
+        cartridge.map_prg(0xC000, 0x4000, Memory::Rom, 1);
+        let chr = cartridge.chr_memory();
+        cartridge.map_chr(0x0000, 0x2000, chr, 0);
         NROM {
-            prg_ram: [0; 0x2000],
-            chr_ram: [0; 0x2000],
-            prg_offset,
-            prg_len,
-            chr_offset,
-            chr_len,
-            mirroring,
+            cartridge,
+            mirroring: header.mirroring,
         } 
     }
 }
@@ -32,35 +29,12 @@ impl fmt::Display for NROM {
 }
 
 impl Mapper for NROM {
-    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
 
-    fn read_chr(&self, rom: *const u8, addr: u16) -> u8 { 
-        if self.chr_len == 0 {
-            self.chr_ram[addr as usize] 
-        } else {
-            unsafe { *(rom.wrapping_add(self.chr_offset + (addr as usize))) } 
-        }
-    }
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
 
-    fn read_prg(&self, rom: *const u8, addr: u16) -> u8 { 
-        match addr {
-            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
-            0x8000..=0xFFFF => {
-                let mut addr = addr - 0x8000;
-                if self.prg_len == 0x4000 && addr >= 0x4000 { addr = addr % 0x4000; }
-                unsafe { *(rom.wrapping_add(self.prg_offset + (addr as usize))) } 
-            },
-            _ => 0
-        }
-    }
     fn write_prg(&mut self, addr: u16, val: u8) { 
-        match addr {
-            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize] = val,
-            _ => ()
-        }
-    }
-
-    fn write_chr(&mut self, addr: u16, val: u8) { 
-        self.chr_ram[(addr) as usize] = val;
+        self.cartridge.write_prg(addr, val);
     }
 }
@@ -0,0 +1,218 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_1: usize = 0x400;
+
+// Jaleco SS88006 (mapper 18). Every bank number is written a nibble at a time.
+// https://www.nesdev.org/wiki/INES_Mapper_018
+pub struct SS88006 {
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    ram_enabled: bool,
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_mask: u16, // Only the low 4, 8, 12 or 16 bits count
+    irq_enabled: bool,
+    irq: bool,
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for SS88006 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SS88006")
+    }
+}
+
+impl SS88006 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut ss88006 = SS88006 {
+            prg_banks: [0, 1, 2],
+            chr_banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            ram_enabled: false,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_mask: 0xFFFF,
+            irq_enabled: false,
+            irq: false,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        ss88006.update_banks();
+        ss88006
+    }
+
+    fn update_banks(&mut self) {
+        if self.ram_enabled {
+            self.cartridge.map_prg(0x6000, PRG_BANK_SIZE_8, Memory::Ram, 0);
+        } else {
+            self.cartridge.unmap_prg(0x6000, PRG_BANK_SIZE_8);
+        }
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[0] as usize);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[1] as usize);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[2] as usize);
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+
+        let chr = self.cartridge.chr_memory();
+        for (n, &bank) in self.chr_banks.iter().enumerate() {
+            self.cartridge.map_chr(n as u16 * 0x400, CHR_BANK_SIZE_1, chr, bank as usize);
+        }
+    }
+
+    // Even addresses write the low nibble, odd ones the high nibble.
+    fn set_nibble(bank: &mut u8, addr: u16, val: u8) {
+        *bank = if addr & 0x01 == 0 { (*bank & 0xF0) | (val & 0x0F) } else { (*bank & 0x0F) | (val & 0x0F) << 4 };
+    }
+}
+
+impl Mapper for SS88006 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers: Vec<(String, u32)> = self.prg_banks.iter().enumerate().map(|(n, &bank)| (format!("prg_{n}"), bank as u32)).collect();
+        registers.extend(self.chr_banks.iter().enumerate().map(|(n, &bank)| (format!("chr_{n}"), bank as u32)));
+        registers.extend([
+            ("ram_enabled".to_string(), self.ram_enabled as u32),
+            ("irq_reload".to_string(), self.irq_reload as u32),
+            ("irq_counter".to_string(), self.irq_counter as u32),
+            ("irq_mask".to_string(), self.irq_mask as u32),
+            ("irq_enabled".to_string(), self.irq_enabled as u32),
+            ("irq".to_string(), self.irq as u32),
+        ]);
+        registers
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr & 0xF003 {
+            0x6000..=0x7FFF => { self.cartridge.write_prg(addr, val); return },
+            0x8000..=0x8003 => SS88006::set_nibble(&mut self.prg_banks[(addr as usize >> 1) & 0x01], addr, val),
+            0x9000..=0x9001 => SS88006::set_nibble(&mut self.prg_banks[2], addr, val),
+            0x9002 => self.ram_enabled = val & 0x01 != 0,
+            0xA000..=0xD003 => {
+                // Two banks per $1000 step: $A000/$A001, $A002/$A003, $B000/$B001...
+                let bank = ((addr as usize - 0xA000) >> 11) | (addr as usize >> 1) & 0x01;
+                SS88006::set_nibble(&mut self.chr_banks[bank], addr, val);
+            },
+            0xE000..=0xE003 => {
+                let shift = (addr & 0x03) * 4;
+                self.irq_reload = (self.irq_reload & !(0x0F << shift)) | ((val & 0x0F) as u16) << shift;
+                return
+            },
+            0xF000 => {
+                self.irq_counter = self.irq_reload;
+                self.irq = false;
+                return
+            },
+            0xF001 => {
+                self.irq_enabled = val & 0x01 != 0;
+                self.irq_mask = match val & 0x0E {
+                    v if v & 0x08 != 0 => 0x000F,
+                    v if v & 0x04 != 0 => 0x00FF,
+                    v if v & 0x02 != 0 => 0x0FFF,
+                    _ => 0xFFFF,
+                };
+                self.irq = false;
+                return
+            },
+            0xF002 => {
+                self.mirroring = match val & 0x03 {
+                    0 => Mirroring::Horizontal,
+                    1 => Mirroring::Vertical,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+                return
+            },
+            _ => return
+        }
+        self.update_banks();
+    }
+
+    // Only the masked bits count down, the IRQ fires when they wrap.
+    fn tick(&mut self) {
+        if !self.irq_enabled { return }
+        let count = self.irq_counter & self.irq_mask;
+        if count == 0 { self.irq = true; }
+        self.irq_counter = (self.irq_counter & !self.irq_mask) | (count.wrapping_sub(1) & self.irq_mask);
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn ss88006() -> SS88006 {
+        let mut header = header(18, 0x40000, 0x40000);
+        header.prg_ram_size = 0x2000;
+        SS88006::new(&header, cartridge(&header))
+    }
+
+    #[test]
+    fn banks() {
+        let mut ss88006 = ss88006();
+        for (addr, value) in [(0x8000, 0x2), (0x8001, 0x1), (0x8002, 0x3), (0x9000, 0x4), (0x9001, 0x1)] {
+            ss88006.write_prg(addr, value);
+        }
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| ss88006.read_prg(addr)), [0x12, 0x03, 0x14, 31]);
+
+        // $A000/$A001 bank 0, $A002/$A003 bank 1, ... $D002/$D003 bank 7.
+        for (n, addr) in [0xA000, 0xA002, 0xB000, 0xB002, 0xC000, 0xC002, 0xD000, 0xD002].into_iter().enumerate() {
+            ss88006.write_prg(addr, 0x0F & n as u8);
+            ss88006.write_prg(addr + 1, 0x0A);
+        }
+        let chr: Vec<u8> = (0..8).map(|n| ss88006.read_chr(n * 0x400)).collect();
+        assert_eq!(chr, [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7]);
+
+        for (value, mirroring) in [(0, Mirroring::Horizontal), (1, Mirroring::Vertical), (2, Mirroring::OneScreenLower), (3, Mirroring::OneScreenUpper)] {
+            ss88006.write_prg(0xF002, value);
+            assert_eq!(ss88006.get_mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn prg_ram() {
+        let mut ss88006 = ss88006();
+        ss88006.get_cartridge_mut().open_bus = 0x60;
+        ss88006.write_prg(0x6000, 0x42);
+        assert_eq!(ss88006.read_prg(0x6000), 0x60);
+        ss88006.write_prg(0x9002, 0x01);
+        ss88006.write_prg(0x6000, 0x42);
+        assert_eq!(ss88006.read_prg(0x6000), 0x42);
+    }
+
+    #[test]
+    fn irq_counter_sizes() {
+        // ($F001 value, ticks until the IRQ, counter after it).
+        for (control, ticks, counter) in [(0x01, 0x1232, 0xFFFF), (0x03, 0x232, 0x1FFF), (0x05, 0x32, 0x12FF), (0x09, 0x2, 0x123F)] {
+            let mut ss88006 = ss88006();
+            for (n, value) in [0x2, 0x3, 0x2, 0x1].into_iter().enumerate() { ss88006.write_prg(0xE000 + n as u16, value); }
+            ss88006.write_prg(0xF000, 0);
+            ss88006.write_prg(0xF001, control);
+            for _ in 0..ticks { ss88006.tick(); }
+            assert!(!ss88006.irq());
+            ss88006.tick();
+            assert!(ss88006.irq());
+            assert_eq!(ss88006.irq_counter, counter);
+            // $F000 and $F001 acknowledge.
+            ss88006.write_prg(0xF000, 0);
+            assert!(!ss88006.irq());
+        }
+
+        // Disabled, the counter holds.
+        let mut ss88006 = ss88006();
+        ss88006.write_prg(0xE000, 0x5);
+        ss88006.write_prg(0xF000, 0);
+        ss88006.tick();
+        assert_eq!(ss88006.irq_counter, 5);
+    }
+}
@@ -0,0 +1,227 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_2: usize = 0x800;
+const CHR_BANK_SIZE_1: usize = 0x400;
+// CPU cycles the TC0690 IRQ trips after the point an MMC3 would.
+const IRQ_DELAY: usize = 4;
+
+// Taito TC0190 (mapper 33) and TC0690 (mapper 48), which moves mirroring
+// to $E000 and adds an MMC3-style scanline IRQ.
+// https://www.nesdev.org/wiki/INES_Mapper_033
+// https://www.nesdev.org/wiki/INES_Mapper_048
+pub struct TC0190 {
+    tc0690: bool,
+    prg_banks: [u8; 2],
+    chr_banks: [u8; 6],
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_delay: usize,
+    irq: bool,
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for TC0190 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tc0690 { write!(f, "TC0690") } else { write!(f, "TC0190") }
+    }
+}
+
+impl TC0190 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut tc0190 = TC0190 {
+            tc0690: header.mapper == 48,
+            prg_banks: [0, 1],
+            chr_banks: [0, 1, 4, 5, 6, 7],
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_delay: 0,
+            irq: false,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        tc0190.update_banks();
+        tc0190
+    }
+
+    fn update_banks(&mut self) {
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[0] as usize);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[1] as usize);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, last.saturating_sub(1));
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+
+        let chr = self.cartridge.chr_memory();
+        self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_2, chr, self.chr_banks[0] as usize);
+        self.cartridge.map_chr(0x0800, CHR_BANK_SIZE_2, chr, self.chr_banks[1] as usize);
+        for n in 0..4 {
+            self.cartridge.map_chr(0x1000 + n as u16 * 0x400, CHR_BANK_SIZE_1, chr, self.chr_banks[2 + n] as usize);
+        }
+    }
+}
+
+impl Mapper for TC0190 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers: Vec<(String, u32)> = self.prg_banks.iter().enumerate().map(|(n, &bank)| (format!("prg_{n}"), bank as u32)).collect();
+        registers.extend(self.chr_banks.iter().enumerate().map(|(n, &bank)| (format!("chr_{n}"), bank as u32)));
+        if self.tc0690 {
+            registers.extend([
+                ("irq_latch".to_string(), self.irq_latch as u32),
+                ("irq_counter".to_string(), self.irq_counter as u32),
+                ("irq_enabled".to_string(), self.irq_enabled as u32),
+                ("irq_delay".to_string(), self.irq_delay as u32),
+                ("irq".to_string(), self.irq as u32),
+            ]);
+        }
+        registers
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr & 0xE003 {
+            0x8000 => {
+                self.prg_banks[0] = val & 0x3F;
+                // TC0190 only, TC0690 has the mirroring bit at $E000.
+                if !self.tc0690 {
+                    self.mirroring = if val & 0x40 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+                }
+            },
+            0x8001 => self.prg_banks[1] = val & 0x3F,
+            0x8002 => self.chr_banks[0] = val,
+            0x8003 => self.chr_banks[1] = val,
+            0xA000..=0xA003 => self.chr_banks[2 + (addr & 0x03) as usize] = val,
+            0xC000 if self.tc0690 => self.irq_latch = val ^ 0xFF,
+            0xC001 if self.tc0690 => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            },
+            0xC002 if self.tc0690 => self.irq_enabled = true,
+            0xC003 if self.tc0690 => {
+                self.irq_enabled = false;
+                self.irq_delay = 0;
+                self.irq = false;
+            },
+            0xE000 if self.tc0690 => {
+                self.mirroring = if val & 0x40 != 0 { Mirroring::Horizontal } else { Mirroring::Vertical };
+            },
+            _ => return
+        }
+        self.update_banks();
+    }
+
+    fn tick(&mut self) {
+        if self.irq_delay > 0 {
+            self.irq_delay -= 1;
+            if self.irq_delay == 0 { self.irq = true; }
+        }
+    }
+
+    fn scanline(&mut self) {
+        if !self.tc0690 { return }
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_delay = IRQ_DELAY;
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn tc0190(mapper: u16) -> TC0190 {
+        let header = header(mapper, 0x20000, 0x40000);
+        TC0190::new(&header, cartridge(&header))
+    }
+
+    #[test]
+    fn banks() {
+        let mut tc0190 = tc0190(33);
+        tc0190.write_prg(0x8000, 0x45);
+        tc0190.write_prg(0x8001, 0x06);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| tc0190.read_prg(addr)), [5, 6, 14, 15]);
+        assert_eq!(tc0190.get_mirroring(), Mirroring::Horizontal);
+
+        // Two 2KB banks, then four 1KB banks.
+        tc0190.write_prg(0x8002, 0x10);
+        tc0190.write_prg(0x8003, 0x11);
+        for n in 0..4 { tc0190.write_prg(0xA000 + n, 0x80 + n as u8); }
+        let chr: Vec<u8> = (0..8).map(|n| tc0190.read_chr(n * 0x400)).collect();
+        assert_eq!(chr, [0x20, 0x21, 0x22, 0x23, 0x80, 0x81, 0x82, 0x83]);
+    }
+
+    #[test]
+    fn tc0690_mirroring() {
+        let mut tc0690 = tc0190(48);
+        tc0690.write_prg(0x8000, 0x45);
+        assert_eq!((tc0690.get_mirroring(), tc0690.read_prg(0x8000)), (Mirroring::Horizontal, 5));
+        tc0690.write_prg(0x8000, 0x00);
+        assert_eq!(tc0690.get_mirroring(), Mirroring::Horizontal);
+        tc0690.write_prg(0xE000, 0x00);
+        assert_eq!(tc0690.get_mirroring(), Mirroring::Vertical);
+        tc0690.write_prg(0xE000, 0x40);
+        assert_eq!(tc0690.get_mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn tc0690_irq() {
+        let mut tc0690 = tc0190(48);
+        // The latch is written inverted.
+        tc0690.write_prg(0xC000, 0xFD);
+        tc0690.write_prg(0xC001, 0);
+        tc0690.write_prg(0xC002, 0);
+        for _ in 0..3 { tc0690.scanline(); }
+        assert_eq!(tc0690.irq_counter, 0);
+        // Trips a few cycles after the scanline.
+        for _ in 1..IRQ_DELAY { tc0690.tick(); }
+        assert!(!tc0690.irq());
+        tc0690.tick();
+        assert!(tc0690.irq());
+        tc0690.write_prg(0xC003, 0);
+        assert!(!tc0690.irq());
+
+        // Acknowledging during the delay drops the IRQ on its way.
+        tc0690.write_prg(0xC002, 0);
+        for _ in 0..3 { tc0690.scanline(); }
+        tc0690.tick();
+        tc0690.write_prg(0xC003, 0);
+        tc0690.write_prg(0xC002, 0);
+        for _ in 0..IRQ_DELAY { tc0690.tick(); }
+        assert!(!tc0690.irq());
+        tc0690.write_prg(0xC003, 0);
+
+        // Disabled, the counter still runs.
+        for _ in 0..3 { tc0690.scanline(); }
+        for _ in 0..IRQ_DELAY { tc0690.tick(); }
+        assert!(!tc0690.irq());
+        assert_eq!(tc0690.irq_counter, 0);
+
+        // TC0190 has no IRQ.
+        let mut tc0190 = tc0190(33);
+        tc0190.write_prg(0xC000, 0xFF);
+        tc0190.write_prg(0xC002, 0);
+        for _ in 0..3 { tc0190.scanline(); }
+        for _ in 0..IRQ_DELAY { tc0190.tick(); }
+        assert!(!tc0190.irq());
+    }
+}
@@ -0,0 +1,200 @@
+use super::{ Header, Mirroring, Timing, Console, LoadError };
+
+// https://www.nesdev.org/wiki/UNIF
+const HEADER_SIZE: usize = 32;
+
+// Board name (without the "NES-", "UNL-", "HVC-", "BTL-", "BMC-" or "MLT-" prefix), mapper, submapper, PRG RAM size.
+const BOARDS: [(&str, u16, u8, usize); 33] = [
+    ("NROM", 0, 0, 0x2000),
+    ("NROM-128", 0, 0, 0x2000),
+    ("NROM-256", 0, 0, 0x2000),
+    ("RROM", 0, 0, 0x2000),
+    ("SAROM", 1, 0, 0x2000),
+    ("SBROM", 1, 0, 0x2000),
+    ("SCROM", 1, 0, 0x2000),
+    ("SEROM", 1, 5, 0x2000),
+    ("SFROM", 1, 0, 0x2000),
+    ("SGROM", 1, 0, 0x2000),
+    ("SHROM", 1, 5, 0x2000),
+    ("SJROM", 1, 0, 0x2000),
+    ("SKROM", 1, 0, 0x2000),
+    ("SLROM", 1, 0, 0x2000),
+    ("SL1ROM", 1, 0, 0x2000),
+    ("SNROM", 1, 0, 0x2000),
+    ("SOROM", 1, 0, 0x4000),
+    ("SUROM", 1, 0, 0x2000),
+    ("SXROM", 1, 0, 0x8000),
+    ("CNROM", 3, 0, 0x2000),
+    ("DEROM", 206, 0, 0),
+    ("DE1ROM", 206, 0, 0),
+    ("DRROM", 206, 0, 0),
+    ("NAMCOT-3433", 88, 0, 0),
+    ("NAMCOT-3425", 95, 0, 0),
+    ("NAMCOT-3446", 76, 0, 0),
+    ("NAMCOT-3453", 154, 0, 0),
+    ("TAITO-TC0190FMC", 33, 0, 0),
+    ("TAITO-TC0190FMC+PAL16R4", 48, 0, 0),
+    ("TAITO-X1-005", 80, 0, 0),
+    ("Ghostbusters63in1", 226, 0, 0),
+    ("Action52", 228, 0, 0),
+    ("42in1ResetSwitch", 233, 0, 0),
+];
+
+pub fn is_unif(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"UNIF")
+}
+
+fn board(name: &str) -> Option<(u16, u8, usize)> {
+    let name = ["NES-", "UNL-", "HVC-", "BTL-", "BMC-", "MLT-"].iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name);
+    BOARDS.iter().find(|(board, ..)| *board == name).map(|&(_, mapper, submapper, prg_ram_size)| (mapper, submapper, prg_ram_size))
+}
+
+// Returns the header and the PRG+CHR image the header offsets point into.
+pub fn parse(bytes: &[u8]) -> Result<(Header, Vec<u8>), LoadError> {
+    let mut prg: [&[u8]; 16] = [&[]; 16];
+    let mut chr: [&[u8]; 16] = [&[]; 16];
+    let mut name = None;
+    let mut mirroring = Mirroring::Horizontal;
+    let mut battery = false;
+
+    let mut i = HEADER_SIZE;
+    while i + 8 <= bytes.len() {
+        let id = &bytes[i..i + 4];
+        let len = u32::from_le_bytes([bytes[i + 4], bytes[i + 5], bytes[i + 6], bytes[i + 7]]) as usize;
+        let Some(data) = bytes.get(i + 8..(i + 8).saturating_add(len)) else {
+            return Err(LoadError::SizeMismatch { expected: (i + 8).saturating_add(len), actual: bytes.len() })
+        };
+        match id {
+            b"MAPR" => {
+                let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                name = Some(String::from_utf8_lossy(&data[..end]).into_owned());
+            },
+            b"MIRR" => mirroring = match data.first() {
+                Some(1) => Mirroring::Vertical,
+                Some(2) => Mirroring::OneScreenLower,
+                Some(3) => Mirroring::OneScreenUpper,
+                Some(4) => Mirroring::FourScreen,
+                _ => Mirroring::Horizontal,
+            },
+            b"BATR" => battery = data.first().map_or(true, |&b| b != 0),
+            [b'P', b'R', b'G', n] | [b'C', b'H', b'R', n] => match (*n as char).to_digit(16) {
+                Some(n) if id[0] == b'P' => prg[n as usize] = data,
+                Some(n) => chr[n as usize] = data,
+                None => ()
+            },
+            _ => ()
+        }
+        i += 8 + len;
+    }
+
+    let name = name.unwrap_or_default();
+    let Some((mapper, submapper, prg_ram_size)) = board(&name) else { return Err(LoadError::UnsupportedBoard(name)) };
+
+    let prg: Vec<u8> = prg.concat();
+    let chr: Vec<u8> = chr.concat();
+    let header = Header {
+        nes2: false,
+        mapper,
+        submapper,
+        mirroring,
+        battery,
+        trainer: false,
+        prg_rom_size: prg.len(),
+        chr_rom_size: chr.len(),
+        prg_ram_size: if battery { 0 } else { prg_ram_size },
+        prg_nvram_size: if battery { prg_ram_size } else { 0 },
+        chr_ram_size: if chr.is_empty() { 0x2000 } else { 0 },
+        chr_nvram_size: 0,
+        prg_offset: 0,
+        chr_offset: prg.len(),
+        timing: Timing::Ntsc,
+        console: Console::Nes,
+        misc_roms: 0,
+        expansion: 0,
+    };
+    Ok((header, [prg, chr].concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        [id.as_slice(), &(data.len() as u32).to_le_bytes(), data].concat()
+    }
+
+    fn unif(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"UNIF".to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend(chunks.concat());
+        bytes
+    }
+
+    #[test]
+    fn banks_are_ordered_by_number() {
+        let bytes = unif(&[
+            chunk(b"MAPR", b"NES-SLROM\0"),
+            chunk(b"CHR1", &[4; 0x1000]),
+            chunk(b"PRG1", &[2; 0x4000]),
+            chunk(b"PRG0", &[1; 0x4000]),
+            chunk(b"CHR0", &[3; 0x1000]),
+        ]);
+        assert!(is_unif(&bytes));
+        let (header, image) = parse(&bytes).unwrap();
+        assert_eq!((header.mapper, header.prg_rom_size, header.chr_rom_size), (1, 0x8000, 0x2000));
+        assert_eq!((header.chr_offset, header.chr_ram_size), (0x8000, 0));
+        assert_eq!([image[0], image[0x4000], image[0x8000], image[0x9000]], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn mirroring_and_battery() {
+        let name = chunk(b"MAPR", b"NES-SNROM\0");
+        let prg = chunk(b"PRG0", &[0; 0x8000]);
+        let (header, _) = parse(&unif(&[name.clone(), prg.clone()])).unwrap();
+        assert_eq!(header.mirroring, Mirroring::Horizontal);
+        assert!(!header.battery);
+        assert_eq!((header.prg_ram_size, header.prg_nvram_size, header.chr_ram_size), (0x2000, 0, 0x2000));
+
+        for (mirr, mirroring) in [(1, Mirroring::Vertical), (2, Mirroring::OneScreenLower), (3, Mirroring::OneScreenUpper), (4, Mirroring::FourScreen)] {
+            let (header, _) = parse(&unif(&[name.clone(), prg.clone(), chunk(b"MIRR", &[mirr])])).unwrap();
+            assert_eq!(header.mirroring, mirroring);
+        }
+
+        let (header, _) = parse(&unif(&[name.clone(), prg.clone(), chunk(b"BATR", &[1])])).unwrap();
+        assert!(header.battery);
+        assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 0x2000));
+        let (header, _) = parse(&unif(&[name, prg, chunk(b"BATR", &[0])])).unwrap();
+        assert!(!header.battery);
+    }
+
+    #[test]
+    fn board_prefixes() {
+        let prg = chunk(b"PRG0", &[0; 0x8000]);
+        for (name, mapper) in [
+            (&b"BMC-42in1ResetSwitch\0"[..], 233),
+            (b"MLT-Action52\0", 228),
+            (b"BMC-Ghostbusters63in1\0", 226),
+            (b"NAMCOT-3453\0", 154),
+            (b"TAITO-X1-005\0", 80),
+            (b"UNL-CNROM\0", 3),
+        ] {
+            let (header, _) = parse(&unif(&[chunk(b"MAPR", name), prg.clone()])).unwrap();
+            assert_eq!(header.mapper, mapper);
+        }
+        assert!(matches!(parse(&unif(&[chunk(b"MAPR", b"UNL-Nothing\0"), prg])), Err(LoadError::UnsupportedBoard(name)) if name == "UNL-Nothing"));
+    }
+
+    #[test]
+    fn truncated_chunks() {
+        let mut bytes = unif(&[chunk(b"MAPR", b"NES-NROM-256\0"), chunk(b"PRG0", &[0; 0x8000])]);
+        let len = bytes.len();
+        bytes.truncate(len - 1);
+        assert!(matches!(parse(&bytes), Err(LoadError::SizeMismatch { expected, actual }) if expected == len && actual == len - 1));
+        // A partial chunk header at the end is ignored.
+        let mut bytes = unif(&[chunk(b"MAPR", b"NES-NROM-256\0"), chunk(b"PRG0", &[0; 0x8000])]);
+        bytes.extend(b"CHR0");
+        assert_eq!(parse(&bytes).unwrap().0.chr_rom_size, 0);
+    }
+}
@@ -0,0 +1,62 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_8: usize = 0x2000;
+
+// VS. UniSystem (mapper 99). Bit 2 of $4016 writes selects the CHR bank,
+// and the first PRG bank on 40KB boards (VS. Gumshoe).
+// https://www.nesdev.org/wiki/INES_Mapper_099
+pub struct VsUnisystem {
+    bank: usize,
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for VsUnisystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VS. UniSystem")
+    }
+}
+
+impl VsUnisystem {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut vs_unisystem = VsUnisystem {
+            bank: 0,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        vs_unisystem.update_banks(0);
+        vs_unisystem
+    }
+
+    fn update_banks(&mut self, bank: usize) {
+        self.bank = bank;
+        self.cartridge.map_prg(0x6000, PRG_BANK_SIZE_8, Memory::Ram, 0);
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, bank * 4);
+        for n in 1..4 {
+            self.cartridge.map_prg(0x8000 + n as u16 * 0x2000, PRG_BANK_SIZE_8, Memory::Rom, n);
+        }
+        let chr = self.cartridge.chr_memory();
+        self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_8, chr, bank);
+    }
+}
+
+impl Mapper for VsUnisystem {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        vec![("bank".to_string(), self.bank as u32)]
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        self.cartridge.write_prg(addr, val)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        if addr == 0x4016 { self.update_banks(((val >> 2) & 0x01) as usize); }
+    }
+}
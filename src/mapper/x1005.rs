@@ -0,0 +1,159 @@
+use super::*;
+use std::fmt;
+
+const PRG_BANK_SIZE_8: usize = 0x2000;
+const CHR_BANK_SIZE_2: usize = 0x800;
+const CHR_BANK_SIZE_1: usize = 0x400;
+const RAM_SIZE: usize = 0x80;
+// Value written to $7EF8 that unlocks the internal RAM.
+const RAM_UNLOCK: u8 = 0xA3;
+
+// Taito X1-005 (mapper 80). Registers live at $7EF0-$7EFF, followed by
+// 128 bytes of (usually battery backed) RAM at $7F00-$7FFF.
+// https://www.nesdev.org/wiki/INES_Mapper_080
+pub struct X1005 {
+    chr_banks: [u8; 6],
+    prg_banks: [u8; 3],
+    ram: [u8; RAM_SIZE],
+    ram_enabled: bool,
+    battery: bool,
+    cartridge: Cartridge,
+    mirroring: Mirroring,
+}
+
+impl fmt::Display for X1005 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "X1-005")
+    }
+}
+
+impl X1005 {
+    pub fn new(header: &Header, cartridge: Cartridge) -> Self {
+        let mut x1005 = X1005 {
+            chr_banks: [0, 2, 4, 5, 6, 7],
+            prg_banks: [0, 1, 2],
+            ram: [0; RAM_SIZE],
+            ram_enabled: false,
+            battery: header.battery,
+            cartridge,
+            mirroring: header.mirroring,
+        };
+        x1005.update_banks();
+        x1005
+    }
+
+    fn update_banks(&mut self) {
+        let last = self.cartridge.prg_banks(Memory::Rom, PRG_BANK_SIZE_8) - 1;
+        self.cartridge.map_prg(0x8000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[0] as usize);
+        self.cartridge.map_prg(0xA000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[1] as usize);
+        self.cartridge.map_prg(0xC000, PRG_BANK_SIZE_8, Memory::Rom, self.prg_banks[2] as usize);
+        self.cartridge.map_prg(0xE000, PRG_BANK_SIZE_8, Memory::Rom, last);
+
+        // 2KB banks ignore the low bit.
+        let chr = self.cartridge.chr_memory();
+        self.cartridge.map_chr(0x0000, CHR_BANK_SIZE_2, chr, (self.chr_banks[0] >> 1) as usize);
+        self.cartridge.map_chr(0x0800, CHR_BANK_SIZE_2, chr, (self.chr_banks[1] >> 1) as usize);
+        for n in 0..4 {
+            self.cartridge.map_chr(0x1000 + n as u16 * 0x400, CHR_BANK_SIZE_1, chr, self.chr_banks[2 + n] as usize);
+        }
+    }
+}
+
+impl Mapper for X1005 {
+    fn get_cartridge(&self) -> &Cartridge { &self.cartridge }
+    fn get_cartridge_mut(&mut self) -> &mut Cartridge { &mut self.cartridge }
+
+    fn get_mirroring(&self) -> Mirroring { self.mirroring }
+
+    fn get_registers(&self) -> Vec<(String, u32)> {
+        let mut registers: Vec<(String, u32)> = self.prg_banks.iter().enumerate().map(|(n, &bank)| (format!("prg_{n}"), bank as u32)).collect();
+        registers.extend(self.chr_banks.iter().enumerate().map(|(n, &bank)| (format!("chr_{n}"), bank as u32)));
+        registers.push(("ram_enabled".to_string(), self.ram_enabled as u32));
+        registers
+    }
+
+    fn read_prg(&mut self, addr: u16) -> u8 {
+        match addr {
+            // The RAM is mirrored once and reads 0 while locked.
+            0x7F00..=0x7FFF if self.ram_enabled => self.ram[addr as usize & (RAM_SIZE - 1)],
+            0x7F00..=0x7FFF => 0,
+            _ => self.cartridge.read_prg(addr)
+        }
+    }
+
+    fn write_prg(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x7EF0..=0x7EF5 => self.chr_banks[(addr - 0x7EF0) as usize] = val,
+            0x7EF6..=0x7EF7 => {
+                self.mirroring = if val & 0x01 != 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+            },
+            0x7EF8..=0x7EF9 => self.ram_enabled = val == RAM_UNLOCK,
+            0x7EFA..=0x7EFF => self.prg_banks[((addr - 0x7EFA) >> 1) as usize] = val,
+            0x7F00..=0x7FFF => {
+                if self.ram_enabled { self.ram[addr as usize & (RAM_SIZE - 1)] = val; }
+                return
+            },
+            _ => return
+        }
+        self.update_banks();
+    }
+
+    fn has_battery(&self) -> bool {
+        self.battery
+    }
+
+    fn get_save_ram(&mut self) -> Option<&mut [u8]> {
+        if self.battery { Some(&mut self.ram) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::test::{ header, cartridge };
+
+    fn x1005(battery: bool) -> X1005 {
+        let mut header = header(80, 0x20000, 0x20000);
+        header.battery = battery;
+        X1005::new(&header, cartridge(&header))
+    }
+
+    #[test]
+    fn banks() {
+        let mut x1005 = x1005(false);
+        x1005.write_prg(0x7EFA, 3);
+        x1005.write_prg(0x7EFD, 4);
+        x1005.write_prg(0x7EFE, 5);
+        assert_eq!([0x8000, 0xA000, 0xC000, 0xE000].map(|addr| x1005.read_prg(addr)), [3, 4, 5, 15]);
+
+        // 2KB banks ignore the low bit.
+        x1005.write_prg(0x7EF0, 0x11);
+        x1005.write_prg(0x7EF1, 0x20);
+        x1005.write_prg(0x7EF5, 0x33);
+        let chr: Vec<u8> = [0x0000, 0x0400, 0x0800, 0x0C00, 0x1C00].iter().map(|&addr| x1005.read_chr(addr)).collect();
+        assert_eq!(chr, [0x10, 0x11, 0x20, 0x21, 0x33]);
+
+        x1005.write_prg(0x7EF6, 0x01);
+        assert_eq!(x1005.get_mirroring(), Mirroring::Vertical);
+        x1005.write_prg(0x7EF7, 0x00);
+        assert_eq!(x1005.get_mirroring(), Mirroring::Horizontal);
+        assert!(x1005.get_save_ram().is_none());
+    }
+
+    #[test]
+    fn ram_protection() {
+        let mut x1005 = x1005(true);
+        // Locked: writes are lost and reads return 0.
+        x1005.write_prg(0x7F00, 0x42);
+        x1005.write_prg(0x7EF8, RAM_UNLOCK);
+        assert_eq!(x1005.read_prg(0x7F00), 0x00);
+        x1005.write_prg(0x7F00, 0x42);
+        // Mirrored once.
+        assert_eq!((x1005.read_prg(0x7F00), x1005.read_prg(0x7F80)), (0x42, 0x42));
+        x1005.write_prg(0x7EF9, 0x00);
+        assert_eq!(x1005.read_prg(0x7F00), 0x00);
+
+        assert!(x1005.has_battery());
+        assert_eq!(x1005.get_save_ram().map(|ram| ram[0]), Some(0x42));
+    }
+}
@@ -14,3 +14,65 @@ pub static COLORS: [u32; 64] = [
    0xFFEFA6FF, 0xFFF79CFF, 0xD7E895FF, 0xA6EDAFFF, 0xA2F2DAFF,
    0x99FFFCFF, 0xDDDDDDFF, 0x111111FF, 0x111111FF
 ];
+
+// RGB PPUs (2C03, 2C04, 2C05): 3 bits per channel, written in octal as 0oRGB.
+// https://www.nesdev.org/wiki/PPU_palettes#2C03_and_2C05
+const RGB_PALETTE: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022, 0o000, 0o000, 0o000,
+    0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140, 0o040, 0o053, 0o044, 0o000, 0o000, 0o000,
+    0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740, 0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o444, 0o000, 0o000,
+    0o777, 0o567, 0o657, 0o757, 0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o666, 0o000, 0o000,
+];
+
+// The 2C04s scramble the palette indices, each version differently. Some colors don't exist on the 2C03.
+// https://www.nesdev.org/wiki/PPU_palettes#2C04
+const RP2C04_PALETTES: [[u16; 64]; 4] = [
+    [
+        0o755, 0o637, 0o700, 0o447, 0o044, 0o120, 0o222, 0o704, 0o777, 0o333, 0o750, 0o503, 0o403, 0o660, 0o320, 0o777,
+        0o357, 0o653, 0o310, 0o360, 0o467, 0o657, 0o764, 0o027, 0o760, 0o276, 0o000, 0o200, 0o666, 0o444, 0o707, 0o014,
+        0o003, 0o567, 0o757, 0o070, 0o077, 0o022, 0o053, 0o507, 0o000, 0o420, 0o747, 0o510, 0o407, 0o006, 0o740, 0o000,
+        0o000, 0o140, 0o555, 0o031, 0o572, 0o326, 0o770, 0o630, 0o020, 0o036, 0o040, 0o111, 0o773, 0o737, 0o430, 0o473,
+    ],
+    [
+        0o000, 0o750, 0o430, 0o572, 0o473, 0o737, 0o044, 0o567, 0o700, 0o407, 0o773, 0o747, 0o777, 0o637, 0o467, 0o040,
+        0o020, 0o357, 0o510, 0o666, 0o053, 0o360, 0o200, 0o447, 0o222, 0o707, 0o003, 0o276, 0o657, 0o320, 0o000, 0o326,
+        0o403, 0o764, 0o740, 0o757, 0o036, 0o310, 0o555, 0o006, 0o507, 0o760, 0o333, 0o120, 0o027, 0o000, 0o660, 0o777,
+        0o653, 0o111, 0o070, 0o630, 0o022, 0o014, 0o704, 0o140, 0o000, 0o077, 0o420, 0o770, 0o755, 0o503, 0o031, 0o444,
+    ],
+    [
+        0o507, 0o737, 0o473, 0o555, 0o040, 0o777, 0o567, 0o120, 0o014, 0o000, 0o764, 0o320, 0o704, 0o666, 0o653, 0o467,
+        0o447, 0o044, 0o503, 0o027, 0o140, 0o430, 0o630, 0o053, 0o333, 0o326, 0o000, 0o006, 0o700, 0o510, 0o747, 0o755,
+        0o637, 0o020, 0o003, 0o770, 0o111, 0o750, 0o740, 0o777, 0o360, 0o403, 0o357, 0o707, 0o036, 0o444, 0o000, 0o310,
+        0o077, 0o200, 0o572, 0o757, 0o420, 0o070, 0o660, 0o222, 0o031, 0o000, 0o657, 0o773, 0o407, 0o276, 0o760, 0o022,
+    ],
+    [
+        0o430, 0o326, 0o044, 0o660, 0o000, 0o755, 0o014, 0o630, 0o555, 0o310, 0o070, 0o003, 0o764, 0o770, 0o040, 0o572,
+        0o737, 0o200, 0o027, 0o747, 0o000, 0o222, 0o510, 0o740, 0o653, 0o053, 0o447, 0o140, 0o403, 0o000, 0o473, 0o357,
+        0o503, 0o031, 0o420, 0o006, 0o407, 0o507, 0o333, 0o704, 0o022, 0o666, 0o036, 0o020, 0o111, 0o773, 0o444, 0o707,
+        0o757, 0o777, 0o320, 0o700, 0o657, 0o750, 0o077, 0o000, 0o276, 0o760, 0o777, 0o120, 0o360, 0o567, 0o637, 0o467,
+    ],
+];
+
+// 3 bits to 8: 7 is 0xFF, 0 is 0x00.
+const fn channel(color: u16, shift: u16) -> u32 {
+    let value = ((color >> shift) & 7) as u32;
+    value << 5 | value << 2 | value >> 1
+}
+
+const fn expand(palette: [u16; 64]) -> [u32; 64] {
+    let mut colors = [0; 64];
+    let mut i = 0;
+    while i < 64 {
+        colors[i] = channel(palette[i], 6) << 24 | channel(palette[i], 3) << 16 | channel(palette[i], 0) << 8 | 0xFF;
+        i += 1;
+    }
+    colors
+}
+
+pub static RGB_COLORS: [u32; 64] = expand(RGB_PALETTE);
+pub static RP2C04_COLORS: [[u32; 64]; 4] = [
+    expand(RP2C04_PALETTES[0]),
+    expand(RP2C04_PALETTES[1]),
+    expand(RP2C04_PALETTES[2]),
+    expand(RP2C04_PALETTES[3]),
+];
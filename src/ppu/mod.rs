@@ -4,11 +4,15 @@ mod ppu_mask;
 mod ppu_status;
 mod colors;
 mod line;
+mod model;
 
-pub use colors::*;
+pub use model::Model;
 use line::{*, Line::*};
 use crate::frame::Frame;
 
+// Frames (about 600ms) a bit of the I/O latch holds its value without being refreshed.
+const IO_LATCH_DECAY: usize = 36;
+
 use crate::mapper::*;
 use self::{
     ppu_addr::PPUAddr,
@@ -17,9 +21,11 @@ use self::{
     ppu_status::PPUStatus,
 };
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct PPU {
+    pub model: Model,
     pub palette_table: [u8; 0x20],
-    vram: [u8; 0x800], // Nametables (2kB)
+    vram: [u8; 0x1000], // Nametables (2kB, 4kB with four-screen cartridge VRAM)
     oam_data: [u8; 0x100],
     sprites: ([u8; 0x20], usize),
     pub oam_addr: u8,
@@ -33,14 +39,26 @@ pub struct PPU {
     line: Line,
     dot: usize,
     pub frame: Frame,
-    pub nmi_occured: bool
+    pub nmi_occured: bool,
+    pub frame_ready: bool, // Set when vblank starts
+    frames: usize,
+    // Last value on the PPU data bus, returned for the bits a register read doesn't drive.
+    io_latch: u8,
+    io_refresh: [usize; 8], // Frame each bit was last refreshed
+}
+
+impl Default for PPU {
+    fn default() -> Self {
+        PPU::new()
+    }
 }
 
 impl PPU {
     pub fn new() -> Self {
         PPU {
+            model: Model::Rp2C02,
             palette_table: [0; 0x20],
-            vram: [0; 0x800],
+            vram: [0; 0x1000],
             oam_data: [0; 0x100],
             sprites: ([0; 0x20], 0),
             oam_addr: 0,
@@ -54,11 +72,15 @@ impl PPU {
             line: Render(0),
             dot: 0,
             frame: Frame::new(),
-            nmi_occured: false
+            nmi_occured: false,
+            frame_ready: false,
+            frames: 0,
+            io_latch: 0,
+            io_refresh: [0; 8],
         }
     }
 
-    pub fn tick(&mut self, rom: *const u8, mapper: &mut Mapper_) {
+    pub fn tick(&mut self, mapper: &mut Mapper_) {
         match self.line {
             PreRender => {
                 if self.dot == 1 { self.status.reset(); }
@@ -67,6 +89,7 @@ impl PPU {
                     if self.dot == 256 { self.addr.coarse_y_increment(); }
                     if self.dot == 257 { self.oam_addr = 0; self.addr.set_horizontal(self.temp); }
                     if self.dot >= 280 && self.dot <= 304 { self.addr.set_vertical(self.temp); }
+                    if self.dot == 260 { mapper.scanline(); }
                 }
             },
             Render(_) => {
@@ -85,9 +108,9 @@ impl PPU {
 
                             let half_pattern_table = self.ctrl.get_background_pattern_addr();
                             let color_addr_1 = half_pattern_table | (tile as u16) << 4 | 1 << 3 | fine_y;
-                            let color_addr_0 = half_pattern_table | (tile as u16) << 4 | 0 << 3 | fine_y;
-                            let color_bit_0 = ( mapper.read_chr(rom, color_addr_0) >> fine_x) & 0x1;
-                            let color_bit_1 = ( mapper.read_chr(rom, color_addr_1) >> fine_x) & 0x1;
+                            let color_addr_0 = half_pattern_table | (tile as u16) << 4 | fine_y;
+                            let color_bit_0 = ( mapper.read_chr(color_addr_0) >> fine_x) & 0x1;
+                            let color_bit_1 = ( mapper.read_chr(color_addr_1) >> fine_x) & 0x1;
                             let color_tile = (color_bit_1 << 1) | color_bit_0;
 
                             let tile_column = (v & 0x001f) as u8;
@@ -117,22 +140,20 @@ impl PPU {
                                     let fine_y = if flip_v { height - 1 - y } else { y } as u16;
                                     let offset = y.div_euclid(8) as u16;
                                     let half_pattern_table = if self.ctrl.is_sprite_size_16() { bank } else { self.ctrl.get_sprite_pattern_addr()};
-                                    let color_addr_0 = half_pattern_table | tile << 4 | 0 << 3 | fine_y;
-                                    let color_bit_0 = ( mapper.read_chr(rom, color_addr_0) >> fine_x) & 0x1;
-                                    let color_addr_1 = half_pattern_table | tile + offset << 4 | 1 << 3 | fine_y;
-                                    let color_bit_1 = ( mapper.read_chr(rom, color_addr_1) >> fine_x) & 0x1;
+                                    let color_addr_0 = half_pattern_table | tile << 4 | fine_y;
+                                    let color_bit_0 = ( mapper.read_chr(color_addr_0) >> fine_x) & 0x1;
+                                    let color_addr_1 = half_pattern_table | (tile + offset) << 4 | 1 << 3 | fine_y;
+                                    let color_bit_1 = ( mapper.read_chr(color_addr_1) >> fine_x) & 0x1;
                                     let color_tile = (color_bit_1 << 1) | color_bit_0;
 
-                                    if color_tile > 0 { 
-                                        if priority || color == 0 { 
-                                            if !self.status.sprite_hit() && self.mask.show_background() { self.status.set_sprite_hit(true); }
-                                            color = (0x10 | palette << 2 | color_tile) as usize 
-                                        } 
+                                    if color_tile > 0 && (priority || color == 0) { 
+                                        if !self.status.sprite_hit() && self.mask.show_background() { self.status.set_sprite_hit(true); }
+                                        color = (0x10 | palette << 2 | color_tile) as usize 
                                     }
                                 }
                             }
                         }
-                        self.frame.set_pixel(COLORS[self.palette_table[color] as usize]);
+                        self.frame.set_pixel(self.model.colors()[self.palette_table[color] as usize]);
                     }
 
                     if self.mask.rendering() {
@@ -160,10 +181,13 @@ impl PPU {
                         }
                     }
                 }
+                if self.mask.rendering() && self.dot == 260 { mapper.scanline(); }
             },
             PostRender(line) => {
                 if line == 241 && self.dot == 1 {
                     self.status.set_vblank(true);
+                    self.frame_ready = true;
+                    self.frames += 1;
                     if self.ctrl.generate_nmi() { 
                         self.nmi_occured = true; 
                     }
@@ -186,8 +210,42 @@ impl PPU {
         self.addr.toggle_latch();
     }
 
+    // Everything but the model (set from the cartridge) is back to its initial state.
+    pub fn power_on(&mut self) {
+        *self = PPU { model: self.model, ..PPU::new() };
+    }
+
+    // The reset line clears PPUCTRL, PPUMASK, the scroll and the write toggle, the memories
+    // (nametables, palette, OAM) and PPUADDR are kept.
+    pub fn reset(&mut self) {
+        self.ctrl = PPUControl::new();
+        self.mask = PPUMask::new();
+        self.temp = 0;
+        self.addr.reset_latch();
+        self.internal_data_buff = 0;
+    }
+
+    // Sets the bits in `mask` of the I/O latch, the others keep decaying.
+    pub fn refresh_io_latch(&mut self, value: u8, mask: u8) {
+        self.io_latch = (self.io_latch & !mask) | (value & mask);
+        for bit in 0..8 {
+            if mask & (1 << bit) != 0 { self.io_refresh[bit] = self.frames; }
+        }
+    }
+
+    pub fn io_latch(&mut self) -> u8 {
+        for bit in 0..8 {
+            if self.frames - self.io_refresh[bit] >= IO_LATCH_DECAY { self.io_latch &= !(1 << bit); }
+        }
+        self.io_latch
+    }
+
     pub fn read_status(&mut self) -> u8 {
-        let status = self.status.bits();
+        let status = match self.model {
+            Model::Rc2C05(id) => (self.status.bits() & 0xE0) | id,
+            _ => (self.status.bits() & 0xE0) | (self.io_latch() & 0x1F)
+        };
+        self.refresh_io_latch(status, 0xE0);
         self.status.set_vblank(false);
         self.addr.reset_latch();
         status
@@ -210,13 +268,19 @@ impl PPU {
         self.addr.increment(self.ctrl.vram_addr_increment());
     }
 
-    pub fn read_oam(&self) -> u8 {
-        self.oam_data[self.oam_addr as usize]
+    // Bits 2-4 of the sprite attributes don't exist and read back as 0.
+    pub fn read_oam(&mut self) -> u8 {
+        let value = match self.oam_addr & 0x03 {
+            2 => self.oam_data[self.oam_addr as usize] & 0xE3,
+            _ => self.oam_data[self.oam_addr as usize]
+        };
+        self.refresh_io_latch(value, 0xFF);
+        value
     }
 
     pub fn write_to_oam(&mut self, value: u8) {
         self.oam_data[self.oam_addr as usize] = value;
-        self.oam_addr += 1;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
     }
 
     pub fn write_data(&mut self, value: u8, mapper: &mut Mapper_) {
@@ -237,13 +301,13 @@ impl PPU {
         }
     }
 
-    pub fn read_data(&mut self, rom: *const u8, mapper: &Mapper_) -> u8 {
+    pub fn read_data(&mut self, mapper: &Mapper_) -> u8 {
         let addr = self.addr.get() & 0x3FFF;
         self.increment_vram_addr();
-        match addr {
+        let value = match addr {
             0..=0x1FFF => {
                 let result = self.internal_data_buff;
-                self.internal_data_buff = mapper.read_chr(rom, addr);
+                self.internal_data_buff = mapper.read_chr(addr);
                 result
             },
             0x2000..=0x2FFF => {
@@ -262,10 +326,46 @@ impl PPU {
                 if addr >= 0x10 && addr % 4 == 0 { 
                     addr -= 0x10; 
                 }
-                self.palette_table[addr as usize]
+                // Palette entries are 6 bits, the top 2 come from the I/O latch.
+                let value = (self.io_latch() & 0xC0) | (self.palette_table[addr as usize] & 0x3F);
+                self.refresh_io_latch(value, 0x3F);
+                return value
             }
             _ => panic!("Unexpected access to mirrored space {}", addr)
-        }
+        };
+        self.refresh_io_latch(value, 0xFF);
+        value
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_latch_decays() {
+        let mut ppu = Box::new(PPU::new());
+        ppu.refresh_io_latch(0xFF, 0x0F);
+        ppu.frames += IO_LATCH_DECAY - 1;
+        assert_eq!(ppu.io_latch(), 0x0F);
+        ppu.refresh_io_latch(0xF0, 0xF0);
+        ppu.frames += 1;
+        assert_eq!(ppu.io_latch(), 0xF0);
+        ppu.frames += IO_LATCH_DECAY;
+        assert_eq!(ppu.io_latch(), 0x00);
+    }
+
+    #[test]
+    fn status_and_oam_reads() {
+        let mut ppu = Box::new(PPU::new());
+        ppu.refresh_io_latch(0x1F, 0xFF);
+        ppu.status.set_vblank(true);
+        assert_eq!(ppu.read_status(), 0x9F);
+        assert_eq!(ppu.read_status(), 0x1F);
+        // Sprite attribute bits 2-4 don't exist.
+        ppu.oam_data[2] = 0xFF;
+        ppu.oam_addr = 2;
+        assert_eq!((ppu.read_oam(), ppu.io_latch()), (0xE3, 0xE3));
+    }
+}
@@ -0,0 +1,62 @@
+use super::colors::{ COLORS, RGB_COLORS, RP2C04_COLORS };
+
+// https://www.nesdev.org/wiki/PPU_variants
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Model {
+    Rp2C02,
+    // RGB PPUs (VS. System, PlayChoice-10).
+    Rp2C03,
+    Rp2C04(u8), // Palette 1-4
+    Rc2C05(u8), // Value in the low bits of $2002, $2000 and $2001 are swapped
+}
+
+impl Model {
+    // NES 2.0 Vs. PPU type.
+    pub fn from_vs_ppu(ppu: u8) -> Self {
+        match ppu {
+            2..=5 => Model::Rp2C04(ppu - 1),
+            8 => Model::Rc2C05(0x1B),
+            9 => Model::Rc2C05(0x3D),
+            10 => Model::Rc2C05(0x1C),
+            11 => Model::Rc2C05(0x1B),
+            12 => Model::Rc2C05(0x00), // 2C05-05, ID unknown
+            _ => Model::Rp2C03,
+        }
+    }
+
+    // Color of each palette index.
+    pub fn colors(&self) -> &'static [u32; 64] {
+        match *self {
+            Model::Rp2C02 => &COLORS,
+            Model::Rp2C04(palette @ 1..=4) => &RP2C04_COLORS[palette as usize - 1],
+            _ => &RGB_COLORS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes() {
+        assert_eq!(Model::from_vs_ppu(0).colors()[0x20], 0xFFFFFFFF);
+        assert_eq!(Model::from_vs_ppu(0).colors()[0x00], 0x6D6D6DFF);
+        assert_eq!(Model::from_vs_ppu(8).colors(), Model::Rp2C03.colors());
+        assert_eq!(Model::Rp2C02.colors(), &COLORS);
+
+        // $2C04-0001 $00 is the 2C03's $35.
+        assert_eq!(Model::from_vs_ppu(2).colors()[0x00], RGB_COLORS[0x35]);
+        assert_eq!(RGB_COLORS[0x35], 0xFFB6B6FF);
+        // The four 2C04s hold the same colors in a different order.
+        let sorted = |model: Model| {
+            let mut colors = model.colors().to_vec();
+            colors.sort();
+            colors
+        };
+        for ppu in 3..=5 {
+            assert_ne!(Model::from_vs_ppu(ppu).colors(), Model::from_vs_ppu(2).colors());
+            assert_eq!(sorted(Model::from_vs_ppu(ppu)), sorted(Model::from_vs_ppu(2)));
+        }
+    }
+}
@@ -13,6 +13,12 @@ bitflags! {
     }
 }
 
+impl Default for PPUMask {
+    fn default() -> Self {
+        PPUMask::new()
+    }
+}
+
 impl PPUMask {
     pub fn new() -> Self {
         PPUMask::empty()
@@ -3,7 +3,6 @@ use bitflags::bitflags;
 bitflags! {
     #[derive(Debug)]
     pub struct PPUStatus: u8 {
-        //TODO: PPU open bus.
         const SPRITE_OVERFLOW   = 0b00100000;
         const SPRITE_HIT        = 0b01000000;
         const VERTICAL_BLANK    = 0b10000000;
@@ -15,10 +14,6 @@ impl PPUStatus {
         PPUStatus::empty()
     }
 
-    pub fn update(&mut self, data: u8) {
-        *self = PPUStatus::from_bits_truncate(data);
-    }
-
     pub fn is_vblank(&self) -> bool {
         self.intersects(PPUStatus::VERTICAL_BLANK)
     }
@@ -35,10 +30,6 @@ impl PPUStatus {
         self.set(PPUStatus::SPRITE_OVERFLOW, cond);
     }
 
-    pub fn sprite_overflow(&self) -> bool {
-        self.intersects(PPUStatus::SPRITE_OVERFLOW)
-    }
-
     pub fn reset(&mut self) {
         self.set_vblank(false);
         self.set_sprite_hit(false);
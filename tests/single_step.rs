@@ -0,0 +1,276 @@
+// Runs every opcode against the single-step tests (NES 6502 variant, no decimal mode):
+// registers, memory and every bus cycle are compared.
+// https://github.com/SingleStepTests/65x02/tree/main/nes6502
+// The vectors aren't in the repository, so the test is ignored by default. Copy the `v1`
+// folder (00.json to ff.json) to tests/single_step or point NASS_SINGLE_STEP at it, then
+// cargo test --release --test single_step -- --ignored
+// Missing vectors fail the test.
+use nass::{ Bus, CPU, Registers };
+use std::{ env, fs, path::PathBuf };
+
+// JAM: the CPU stops after reading its operand and then reads $FFFF forever, the vectors
+// alternate between $FFFE and $FFFF. Only the first two cycles are compared exactly.
+const JAM: [u8; 12] = [0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2];
+// B and bit 5 aren't flip-flops on the 6502, only what PHP/BRK push.
+const P_MASK: u8 = 0xCF;
+// Failures printed per opcode, the rest are only counted.
+const MAX_REPORTS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+// 64KB of RAM and nothing else, every access is logged.
+struct FlatBus {
+    ram: Vec<u8>,
+    accesses: Vec<(u16, u8, Access)>,
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.ram[addr as usize];
+        self.accesses.push((addr, value, Access::Read));
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+        self.accesses.push((addr, value, Access::Write));
+    }
+
+    fn tick(&mut self) {}
+    fn nmi(&mut self) -> bool { false }
+    fn irq(&self) -> bool { false }
+}
+
+// Just enough JSON for the vectors: objects, arrays, strings and integers.
+#[derive(Debug)]
+enum Json {
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &[u8]) -> Result<Json, String> {
+        let mut pos = 0;
+        let json = Json::value(text, &mut pos)?;
+        Json::skip_whitespace(text, &mut pos);
+        if pos != text.len() { return Err(format!("Trailing data at {pos}")) }
+        Ok(json)
+    }
+
+    fn skip_whitespace(text: &[u8], pos: &mut usize) {
+        while *pos < text.len() && text[*pos].is_ascii_whitespace() { *pos += 1; }
+    }
+
+    fn expect(text: &[u8], pos: &mut usize, byte: u8) -> Result<(), String> {
+        Json::skip_whitespace(text, pos);
+        if text.get(*pos) != Some(&byte) { return Err(format!("Expected '{}' at {}", byte as char, pos)) }
+        *pos += 1;
+        Ok(())
+    }
+
+    fn value(text: &[u8], pos: &mut usize) -> Result<Json, String> {
+        Json::skip_whitespace(text, pos);
+        match text.get(*pos) {
+            Some(b'{') => {
+                *pos += 1;
+                let mut fields = Vec::new();
+                Json::skip_whitespace(text, pos);
+                if text.get(*pos) == Some(&b'}') { *pos += 1; return Ok(Json::Object(fields)) }
+                loop {
+                    Json::skip_whitespace(text, pos);
+                    let key = match Json::value(text, pos)? {
+                        Json::String(key) => key,
+                        _ => return Err(format!("Expected a key at {pos}"))
+                    };
+                    Json::expect(text, pos, b':')?;
+                    fields.push((key, Json::value(text, pos)?));
+                    Json::skip_whitespace(text, pos);
+                    match text.get(*pos) {
+                        Some(b',') => *pos += 1,
+                        Some(b'}') => { *pos += 1; return Ok(Json::Object(fields)) },
+                        _ => return Err(format!("Expected ',' or '}}' at {pos}"))
+                    }
+                }
+            },
+            Some(b'[') => {
+                *pos += 1;
+                let mut items = Vec::new();
+                Json::skip_whitespace(text, pos);
+                if text.get(*pos) == Some(&b']') { *pos += 1; return Ok(Json::Array(items)) }
+                loop {
+                    items.push(Json::value(text, pos)?);
+                    Json::skip_whitespace(text, pos);
+                    match text.get(*pos) {
+                        Some(b',') => *pos += 1,
+                        Some(b']') => { *pos += 1; return Ok(Json::Array(items)) },
+                        _ => return Err(format!("Expected ',' or ']' at {pos}"))
+                    }
+                }
+            },
+            // Test names are plain ASCII, escapes are kept as they are.
+            Some(b'"') => {
+                *pos += 1;
+                let start = *pos;
+                while *pos < text.len() && text[*pos] != b'"' {
+                    if text[*pos] == b'\\' { *pos += 1; }
+                    *pos += 1;
+                }
+                let string = String::from_utf8_lossy(&text[start..(*pos).min(text.len())]).into_owned();
+                Json::expect(text, pos, b'"')?;
+                Ok(Json::String(string))
+            },
+            Some(b'-' | b'0'..=b'9') => {
+                let start = *pos;
+                *pos += 1;
+                while *pos < text.len() && text[*pos].is_ascii_digit() { *pos += 1; }
+                std::str::from_utf8(&text[start..*pos]).unwrap().parse().map(Json::Number).map_err(|error| format!("{error} at {start}"))
+            },
+            _ => Err(format!("Unexpected data at {pos}"))
+        }
+    }
+
+    fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+                .unwrap_or_else(|| panic!("Missing field {key}")),
+            _ => panic!("Not an object looking for {key}")
+        }
+    }
+
+    fn number(&self) -> i64 {
+        match self {
+            Json::Number(value) => *value,
+            _ => panic!("Not a number: {self:?}")
+        }
+    }
+
+    fn string(&self) -> &str {
+        match self {
+            Json::String(value) => value,
+            _ => panic!("Not a string: {self:?}")
+        }
+    }
+
+    fn array(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => panic!("Not an array: {self:?}")
+        }
+    }
+}
+
+struct State {
+    registers: Registers,
+    ram: Vec<(u16, u8)>,
+}
+
+impl State {
+    fn new(json: &Json) -> Self {
+        let byte = |key| json.get(key).number() as u8;
+        State {
+            registers: Registers {
+                a: byte("a"),
+                x: byte("x"),
+                y: byte("y"),
+                s: byte("s"),
+                p: byte("p"),
+                pc: json.get("pc").number() as u16,
+            },
+            ram: json.get("ram").array().iter().map(|cell| {
+                let cell = cell.array();
+                (cell[0].number() as u16, cell[1].number() as u8)
+            }).collect(),
+        }
+    }
+}
+
+// Runs one vector, returns what differs.
+fn run(test: &Json) -> Result<(), String> {
+    let initial = State::new(test.get("initial"));
+    let expected = State::new(test.get("final"));
+    let cycles: Vec<(u16, u8, Access)> = test.get("cycles").array().iter().map(|cycle| {
+        let cycle = cycle.array();
+        let access = match cycle[2].string() { "write" => Access::Write, _ => Access::Read };
+        (cycle[0].number() as u16, cycle[1].number() as u8, access)
+    }).collect();
+
+    let mut bus = FlatBus { ram: vec![0; 0x10000], accesses: Vec::new() };
+    for &(addr, value) in initial.ram.iter() { bus.ram[addr as usize] = value; }
+    let mut cpu = CPU::new(bus);
+    cpu.set_registers(initial.registers);
+    cpu.step_instruction();
+
+    let mut errors = Vec::new();
+    let jam = cpu.halted.is_some();
+    if jam {
+        // The halted CPU does one read per step.
+        while cpu.bus.accesses.len() < cycles.len() { cpu.step_instruction(); }
+        cpu.bus.accesses.truncate(cycles.len());
+    }
+    if JAM.contains(&cpu.bus.ram[initial.registers.pc as usize]) != jam {
+        errors.push(format!("halted {:?}", cpu.halted));
+    }
+    let registers = cpu.get_registers();
+    let mut expected_registers = expected.registers;
+    expected_registers.p &= P_MASK;
+    // Where PC ends up while halted is up to the emulator.
+    if jam { expected_registers.pc = registers.pc; }
+    if (Registers { p: registers.p & P_MASK, ..registers }) != expected_registers {
+        errors.push(format!("registers {:02X?}, expected {:02X?}", registers, expected.registers));
+    }
+    for &(addr, value) in expected.ram.iter() {
+        if cpu.bus.ram[addr as usize] != value {
+            errors.push(format!("${:04X} = ${:02X}, expected ${:02X}", addr, cpu.bus.ram[addr as usize], value));
+        }
+    }
+    let same_cycles = if jam {
+        cpu.bus.accesses.len() == cycles.len() && cpu.bus.accesses[..2] == cycles[..2]
+            && cpu.bus.accesses.iter().zip(&cycles).all(|(access, cycle)| access.2 == cycle.2)
+    } else {
+        cpu.bus.accesses == cycles
+    };
+    if !same_cycles {
+        errors.push(format!("cycles {:04X?}, expected {:04X?}", cpu.bus.accesses, cycles));
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n    ")) }
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests nes6502 vectors, see the top of the file"]
+fn single_step() {
+    let dir = env::var_os("NASS_SINGLE_STEP").map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/single_step"));
+    assert!(dir.is_dir(), "{} not found, see the top of tests/single_step.rs", dir.display());
+
+    let (mut passed, mut failed, mut missing) = (0, 0, Vec::new());
+    for opcode in 0..=0xFFu8 {
+        let path = dir.join(format!("{:02x}.json", opcode));
+        let Ok(text) = fs::read(&path) else {
+            missing.push(opcode);
+            continue
+        };
+        let tests = Json::parse(&text).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+        let mut reports = 0;
+        for test in tests.array() {
+            match run(test) {
+                Ok(()) => passed += 1,
+                Err(error) => {
+                    failed += 1;
+                    if reports < MAX_REPORTS {
+                        eprintln!("${:02X} {}:\n    {}", opcode, test.get("name").string(), error);
+                        reports += 1;
+                    }
+                }
+            }
+        }
+    }
+    eprintln!("{passed} passed, {failed} failed.");
+    assert!(missing.is_empty(), "No vectors for opcodes {missing:02X?}");
+    assert_eq!(failed, 0);
+}